@@ -0,0 +1,297 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cli::Mode,
+    config::{AppConfig, LayerConfig},
+    layers::{self, NONE, RARITIES},
+    metadata,
+};
+
+/// Something wrong with the project, and the file it was found in
+#[derive(Debug)]
+pub struct Problem {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    problems: Vec<Problem>,
+    /// The size of the first image, every other one has to match it
+    size: Option<(u32, u32, PathBuf)>,
+    /// The traits found for each layer, across sets
+    traits: HashMap<String, HashSet<String>>,
+}
+
+impl Checker {
+    fn report(&mut self, path: &Path, message: impl Into<String>) {
+        self.problems.push(Problem {
+            path: path.to_path_buf(),
+            message: message.into(),
+        });
+    }
+
+    fn check_image(&mut self, path: &Path) -> bool {
+        let (width, height) = match image::image_dimensions(path) {
+            Ok(dimensions) => dimensions,
+            Err(e) => {
+                self.report(path, format!("failed to load image: {}", e));
+
+                return false;
+            }
+        };
+
+        match &self.size {
+            None => self.size = Some((width, height, path.to_path_buf())),
+            Some((expected_width, expected_height, first))
+                if (*expected_width, *expected_height) != (width, height) =>
+            {
+                let message = format!(
+                    "image is {}x{} but {} is {}x{}",
+                    width,
+                    height,
+                    first.display(),
+                    expected_width,
+                    expected_height
+                );
+
+                self.report(path, message);
+            }
+            Some(_) => (),
+        }
+
+        true
+    }
+
+    fn check_layer(&mut self, mode: Mode, layer: &LayerConfig, layer_path: &Path) {
+        if !layer_path.is_dir() {
+            self.report(
+                layer_path,
+                format!("missing folder for layer {}", layer.name),
+            );
+
+            return;
+        }
+
+        let mut found = Vec::new();
+
+        match mode {
+            Mode::Advanced => {
+                for path in pngs(layer_path) {
+                    if !self.check_image(&path) {
+                        continue;
+                    }
+
+                    let file_name = stem(&path);
+
+                    // parsed just like gen does, so check reports exactly what gen would trip over
+                    match layers::parse_trait_file_name(&file_name) {
+                        Ok((name, _)) => found.push(name.to_owned()),
+                        Err(e) => self.report(&path, e.to_string()),
+                    }
+                }
+            }
+            Mode::Simple => {
+                for path in entries(layer_path).filter(|path| path.is_dir()) {
+                    if !RARITIES.iter().any(|rarity| path.ends_with(rarity)) {
+                        let message = format!(
+                            "not a rarity folder, expected one of {}",
+                            RARITIES.join(", ")
+                        );
+
+                        self.report(&path, message);
+
+                        continue;
+                    }
+
+                    for trait_path in pngs(&path) {
+                        if self.check_image(&trait_path) {
+                            found.push(stem(&trait_path));
+                        }
+                    }
+                }
+            }
+        }
+
+        if found.iter().any(|name| name == NONE) {
+            let message = format!(
+                "layer {} has a trait named {}, which is reserved for leaving the layer out",
+                layer.name, NONE
+            );
+
+            self.report(layer_path, message);
+        }
+
+        if found.is_empty() && layer.none.is_none() {
+            self.report(layer_path, format!("layer {} has no traits", layer.name));
+        }
+
+        self.traits
+            .entry(layer_name(layer).to_owned())
+            .or_default()
+            .extend(found);
+    }
+
+    fn check_exclusions(&mut self, config: &AppConfig, layers: &[LayerConfig], config_path: &Path) {
+        let set_names: Vec<&str> = config
+            .sets
+            .iter()
+            .flatten()
+            .map(|set| set.name.as_str())
+            .collect();
+
+        for (position, layer) in layers.iter().enumerate() {
+            for set in layer.exclude_if_sets.iter().flatten() {
+                if !set_names.contains(&set.as_str()) {
+                    let message = format!("layer {} excludes unknown set {}", layer.name, set);
+
+                    self.report(config_path, message);
+                }
+            }
+
+            for if_trait in layer.exclude_if_traits.iter().flatten() {
+                let target = layers
+                    .iter()
+                    .position(|other| layer_name(other) == if_trait.layer);
+
+                match target {
+                    None => {
+                        let message = format!(
+                            "layer {} excludes traits of unknown layer {}",
+                            layer.name, if_trait.layer
+                        );
+
+                        self.report(config_path, message);
+                    }
+                    Some(target) if target >= position => {
+                        let message = format!(
+                            "layer {} can only exclude traits of layers before it, not {}",
+                            layer.name, if_trait.layer
+                        );
+
+                        self.report(config_path, message);
+                    }
+                    Some(_) => {
+                        let known = self
+                            .traits
+                            .get(&if_trait.layer)
+                            .cloned()
+                            .unwrap_or_default();
+
+                        for name in &if_trait.traits {
+                            if name != NONE && !known.contains(name) {
+                                let message = format!(
+                                    "layer {} excludes unknown trait {} of layer {}",
+                                    layer.name, name, if_trait.layer
+                                );
+
+                                self.report(config_path, message);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Check the config and every layer, returning all the problems found
+pub fn check(config: &AppConfig, config_path: &Path) -> Vec<Problem> {
+    let mut checker = Checker::default();
+
+    let validations = [
+        config.validate_policy(config_path).map(|_| ()),
+        config.validate_sets(),
+        config.validate_metaplex(),
+        config.validate_attributes(),
+        metadata::validate_templates(config),
+    ];
+
+    for error in validations.into_iter().filter_map(Result::err) {
+        checker.report(config_path, format!("{:#}", error));
+    }
+
+    let roots = match &config.sets {
+        Some(sets) => {
+            let total: usize = sets.iter().map(|set| set.amount).sum();
+
+            if total != config.amount {
+                let message = format!(
+                    "amount in sets adds up to {} but amount is {}",
+                    total, config.amount
+                );
+
+                checker.report(config_path, message);
+            }
+
+            sets.iter()
+                .map(|set| (config.path.join(&set.name), set.layers(&config.layers)))
+                .collect()
+        }
+        None => vec![(config.path.clone(), config.layers.clone())],
+    };
+
+    for (root, layers) in &roots {
+        if !root.is_dir() {
+            checker.report(root, "missing images folder");
+
+            continue;
+        }
+
+        for layer in layers {
+            checker.check_layer(config.mode, layer, &root.join(&layer.name));
+        }
+    }
+
+    checker.check_exclusions(config, &config.layers, config_path);
+
+    for set in config.sets.iter().flatten() {
+        if let Some(layers) = &set.layers {
+            checker.check_exclusions(config, layers, config_path);
+        }
+    }
+
+    checker.problems
+}
+
+/// The name traits of a layer are recorded under
+fn layer_name(layer: &LayerConfig) -> &str {
+    layer.display_name.as_deref().unwrap_or(&layer.name)
+}
+
+fn entries(folder: &Path) -> impl Iterator<Item = PathBuf> {
+    let mut paths: Vec<PathBuf> = folder
+        .read_dir()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+
+    paths.sort();
+
+    paths.into_iter()
+}
+
+fn pngs(folder: &Path) -> impl Iterator<Item = PathBuf> {
+    entries(folder)
+        .filter(|path| path.is_file())
+        .filter(|path| matches!(path.extension(), Some(ext) if ext == "png"))
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_owned()
+}
@@ -1,8 +1,15 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf, str::FromStr};
 
 use clap::{ArgEnum, Parser};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    config::{self, ConfigFormat, NftMakerNetwork},
+    nft_maker::Policy,
+    policy,
+};
+
 #[derive(Parser, Debug)]
 pub struct NewCommand {
     /// The mode for processing attribute rarity
@@ -12,26 +19,319 @@ pub struct NewCommand {
 
 #[derive(Parser, Debug)]
 pub struct ConfigArgs {
-    /// Path to the projects config file
-    #[clap(short, long, default_value = "pix.json")]
-    pub config: String,
+    /// Path to the projects config file, pix.json, pix.yaml or pix.toml by default
+    #[clap(short, long)]
+    pub config: Option<String>,
+}
+
+impl ConfigArgs {
+    /// The config file given, or the one found in the current directory
+    pub fn path(&self) -> &str {
+        match &self.config {
+            Some(config) => config,
+            None => config::find_config(),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct NewArgs {
+    pub name: String,
+    /// When the policy locks, like 2027-01-31 or 2027-01-31T12:00:00Z, a year from now by default
+    #[clap(long)]
+    pub policy_locks_at: Option<String>,
+    /// The network to create the project on
+    #[clap(long, arg_enum)]
+    pub network: Option<NftMakerNetwork>,
+    /// How many of each token can be minted
+    #[clap(long)]
+    pub max_supply: Option<i32>,
+    /// The wallet that receives the sales
+    #[clap(long)]
+    pub payout_wallet: Option<String>,
+    /// The project description
+    #[clap(long)]
+    pub description: Option<String>,
+    /// The project website
+    #[clap(long)]
+    pub project_url: Option<String>,
+    /// Put in front of every asset name on chain
+    #[clap(long)]
+    pub tokenname_prefix: Option<String>,
+    /// The pix auth profile to create the project with
+    #[clap(long)]
+    pub profile: Option<String>,
+    /// The format to write the config in
+    #[clap(long, arg_enum, default_value = "json")]
+    pub format: ConfigFormat,
+    #[clap(flatten)]
+    pub policy: PolicyArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct PolicyArgs {
+    /// A native script made with cardano-cli to use as the policy, instead of letting nft-maker.io create one
+    #[clap(long, requires_all = &["policy-skey", "policy-vkey"])]
+    pub policy_script: Option<PathBuf>,
+    /// The policy's signing key file
+    #[clap(long, requires = "policy-script")]
+    pub policy_skey: Option<PathBuf>,
+    /// The policy's verification key file
+    #[clap(long, requires = "policy-script")]
+    pub policy_vkey: Option<PathBuf>,
+}
+
+impl PolicyArgs {
+    /// The policy to create the project with, an empty one lets nft-maker.io generate it
+    pub fn load(&self, policy_id: Option<&str>) -> anyhow::Result<Policy> {
+        match (&self.policy_script, &self.policy_skey, &self.policy_vkey) {
+            (Some(script), Some(skey), Some(vkey)) => {
+                policy::load_policy(script, skey, vkey, policy_id)
+            }
+            _ => Ok(Policy::default()),
+        }
+    }
+
+    /// The native script of the policy brought along, if any
+    pub fn script(&self) -> anyhow::Result<Option<policy::NativeScript>> {
+        self.policy_script
+            .as_deref()
+            .map(policy::NativeScript::load)
+            .transpose()
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct GenArgs {
+    #[clap(flatten)]
+    pub config_args: ConfigArgs,
+    /// Seed for the random generator, a random one is picked and recorded when omitted
+    #[clap(short, long)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Parser, Debug)]
+pub struct UploadArgs {
+    #[clap(flatten)]
+    pub config_args: ConfigArgs,
+    /// Only upload the tokens that failed last time
+    #[clap(long)]
+    pub retry_failed: bool,
+    /// Delete these tokens from nft-maker.io and upload them again, like `1-5,8`
+    #[clap(long, conflicts_with = "retry-failed")]
+    pub replace: Option<TokenRanges>,
+    /// How many uploads to run at the same time
+    #[clap(long, default_value = "1")]
+    pub concurrency: usize,
+    /// Send attribute placeholders for the project template, or each token's full metadata
+    #[clap(long, arg_enum, default_value = "placeholders")]
+    pub metadata: UploadMetadata,
+}
+
+#[derive(Parser, Debug)]
+pub enum RemoteCommand {
+    /// Create the project on nft-maker.io and save it to the config
+    Create {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
+        #[clap(flatten)]
+        policy: PolicyArgs,
+    },
+    /// List the project's NFTs on nft-maker.io next to the local output
+    List(RemoteListArgs),
+    /// Delete tokens from nft-maker.io, refusing sold or minted ones
+    Delete {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
+        /// The tokens to delete, like `1-5,8`
+        tokens: TokenRanges,
+    },
+    /// Show everything nft-maker.io knows about a token
+    Show {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
+        /// The token number
+        token: usize,
+    },
+}
+
+impl RemoteCommand {
+    pub fn config(&self) -> &str {
+        match self {
+            Self::Create { config_args, .. }
+            | Self::Delete { config_args, .. }
+            | Self::Show { config_args, .. } => config_args.path(),
+            Self::List(args) => args.config_args.path(),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub enum ConfigCommand {
+    /// Write the config in another format, next to the original
+    Convert {
+        #[clap(flatten)]
+        config_args: ConfigArgs,
+        /// The format to convert to
+        #[clap(long, arg_enum)]
+        to: ConfigFormat,
+        /// Where to write the converted config, pix.<format> next to the original by default
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub enum ImportCommand {
+    /// Import a HashLips art engine project, its src/config.js and layers/ folder
+    Hashlips {
+        /// The HashLips project folder
+        dir: PathBuf,
+        /// Where to create the pix project
+        #[clap(short, long, default_value = ".")]
+        output: PathBuf,
+        /// The format to write the config in
+        #[clap(long, arg_enum, default_value = "json")]
+        format: ConfigFormat,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub struct RemoteListArgs {
+    #[clap(flatten)]
+    pub config_args: ConfigArgs,
+    /// Only list NFTs in this state
+    #[clap(long, arg_enum, default_value = "all")]
+    pub state: NftState,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum NftState {
+    All,
+    Free,
+    Reserved,
+    Sold,
+    Error,
+}
+
+impl Display for NftState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use NftState::*;
+
+        match self {
+            All => write!(f, "all"),
+            Free => write!(f, "free"),
+            Reserved => write!(f, "reserved"),
+            Sold => write!(f, "sold"),
+            Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Token numbers given as ranges, like `1-5,8,10-12`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TokenRanges(pub Vec<usize>);
+
+impl TokenRanges {
+    /// More tokens than any collection has, so a typo can't expand into billions of numbers
+    pub const MAX: usize = 100_000;
+}
+
+impl FromStr for TokenRanges {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |number: &str| {
+            number
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| format!("`{}` is not a token number", number.trim()))
+        };
+
+        let mut numbers = Vec::new();
+
+        for range in s.split(',').filter(|range| !range.trim().is_empty()) {
+            match range.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (parse(start)?, parse(end)?);
+
+                    if start > end {
+                        return Err(format!("`{}` is not a valid range", range.trim()));
+                    }
+
+                    if end - start >= Self::MAX.saturating_sub(numbers.len()) {
+                        return Err(format!(
+                            "`{}` is too many tokens, at most {} can be given",
+                            range.trim(),
+                            Self::MAX
+                        ));
+                    }
+
+                    numbers.extend(start..=end);
+                }
+                None => numbers.push(parse(range)?),
+            }
+        }
+
+        if numbers.is_empty() {
+            return Err(String::from("no tokens given"));
+        }
+
+        numbers.sort_unstable();
+        numbers.dedup();
+
+        Ok(Self(numbers))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum UploadMetadata {
+    Placeholders,
+    Full,
 }
 
 /// A CLI for managing NFT projects
 #[derive(Parser, Debug)]
 pub enum Commands {
     /// Provide your NFT Maker API Key to use globally
-    Auth,
+    Auth {
+        /// Save the key under this name, for projects that set `profile`
+        #[clap(long)]
+        profile: Option<String>,
+    },
+    /// Check the config and layers for problems before generating
+    Check(ConfigArgs),
     /// Clean the output directory
     Clean,
     /// Generate an NFT collection
-    Gen(ConfigArgs),
+    Gen(GenArgs),
+    /// Turn a project made with another tool into a pix project
+    Import {
+        #[clap(subcommand)]
+        command: ImportCommand,
+    },
     /// Output metadata template that can be uploaded to nft-maker.io
     Metadata(ConfigArgs),
     /// Create a new project
-    New { name: String },
+    New(NewArgs),
+    /// Check the policy script and show its policy id and time lock
+    Policy(ConfigArgs),
+    /// Print the JSON Schema of pix.json, for editor autocompletion
+    Schema,
+    /// Work with the project's config file
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Inspect the project on nft-maker.io
+    Remote {
+        #[clap(subcommand)]
+        command: RemoteCommand,
+    },
     /// Upload an NFT collection to nft-maker.io
-    Upload(ConfigArgs),
+    Upload(UploadArgs),
+    /// Check the output against the collection manifest
+    Verify(ConfigArgs),
 }
 
 impl Default for Commands {
@@ -46,13 +346,14 @@ impl Commands {
     }
 }
 
-#[derive(Clone, Copy, Debug, ArgEnum, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, ArgEnum, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Mode {
     Simple,
     Advanced,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for Mode {
     fn default() -> Self {
         Mode::Simple
@@ -1,18 +1,38 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{anyhow, Context};
+use chrono::{DateTime, Duration, NaiveDate, SecondsFormat, TimeZone, Utc};
+use clap::ArgEnum;
 use config::{Config, File};
 use dialoguer::{console::Term, theme::ColorfulTheme, Confirm, Input, Select};
 use directories_next::ProjectDirs;
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Schema, SchemaObject},
+    JsonSchema,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
-use crate::cli::Mode;
+use crate::{
+    cli::{Mode, NewArgs},
+    policy::{self, NativeScript},
+    schema,
+};
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default)]
 pub struct AppConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub policy_id: Option<String>,
+    /// The native script `policy_id` is checked against
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy_script: Option<PathBuf>,
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub display_name: Option<String>,
@@ -24,19 +44,121 @@ pub struct AppConfig {
     pub path: PathBuf,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sets: Option<Vec<SetConfig>>,
+    /// Write each token's set into its attributes under this name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set_attribute: Option<String>,
     pub layers: Vec<LayerConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<Vec<AttributeConfig>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub nft_maker: Option<NftMakerLocalConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nft_maker_project: Option<NftMakerProjectConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<MetadataConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metaplex: Option<MetaplexConfig>,
+    /// The `pix auth` profile whose API key the project uses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub extra: Option<Map<String, Value>>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default)]
+pub struct MetadataConfig {
+    #[serde(default)]
+    pub format: MetadataFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_uri: Option<String>,
+    #[serde(default)]
+    pub json_extension: bool,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataFormat {
+    #[default]
+    Cip25,
+    Erc721,
+    Metaplex,
+}
+
+impl Display for MetadataFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cip25 => write!(f, "cip25"),
+            Self::Erc721 => write!(f, "erc721"),
+            Self::Metaplex => write!(f, "metaplex"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default)]
+pub struct MetaplexConfig {
+    #[serde(default)]
+    pub symbol: String,
+    #[serde(default)]
+    pub seller_fee_basis_points: u16,
+    #[serde(default)]
+    pub creators: Vec<CreatorConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<MetaplexCollection>,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone)]
+pub struct CreatorConfig {
+    pub address: String,
+    pub share: u8,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone)]
+pub struct MetaplexCollection {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default)]
 pub struct SetConfig {
     pub name: String,
+    /// Names the set's tokens instead of the top level `display_name`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
     pub amount: usize,
+    /// The set's own layers, in their own order, instead of the top level ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layers: Option<Vec<LayerConfig>>,
+    /// `none` weights by layer name, overriding the layers' own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub none: Option<BTreeMap<String, u32>>,
+    /// Merged over the top level `extra` for the set's tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra: Option<Map<String, Value>>,
+}
+
+impl SetConfig {
+    /// The layers the set's tokens are made of, with its `none` weights applied
+    pub fn layers(&self, defaults: &[LayerConfig]) -> Vec<LayerConfig> {
+        let mut layers = self.layers.clone().unwrap_or_else(|| defaults.to_vec());
+
+        for layer in &mut layers {
+            if let Some(weight) = self.none.as_ref().and_then(|none| none.get(&layer.name)) {
+                layer.none = Some(*weight);
+            }
+        }
+
+        layers
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone)]
 pub struct LayerConfig {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -47,20 +169,275 @@ pub struct LayerConfig {
     pub exclude_if_sets: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exclude_if_traits: Option<Vec<IfTrait>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+/// An attribute that isn't drawn from a layer, like a numeric stat
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone)]
+pub struct AttributeConfig {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    pub min: i64,
+    pub max: i64,
 }
 
-#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone)]
 pub struct IfTrait {
     pub layer: String,
     pub traits: Vec<String>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default)]
 pub struct NftMakerLocalConfig {
     pub network: NftMakerNetwork,
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub apikey: String,
     pub nft_project_id: NftProjectId,
+    /// Overrides the network's API url, e.g. for a proxy or a local test server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Use the legacy API that takes the API key in the url
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub legacy_api: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests_per_second: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subfiles: Option<Vec<SubfileConfig>>,
+    /// The `pix auth` profile the API key was looked up in, when the config was loaded
+    #[serde(skip)]
+    pub profile: Option<String>,
+}
+
+/// The settings a project is created with on NFT Maker
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone)]
+pub struct NftMakerProjectConfig {
+    #[serde(default)]
+    pub network: NftMakerNetwork,
+    /// When the policy locks, as an RFC 3339 date time
+    pub policy_locks_at: String,
+    /// How many of each token can be minted
+    #[serde(default = "default_max_supply")]
+    pub max_supply: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payout_wallet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_url: Option<String>,
+    /// Put in front of every asset name on chain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokenname_prefix: Option<String>,
+}
+
+fn default_max_supply() -> i32 {
+    1
+}
+
+impl Default for NftMakerProjectConfig {
+    fn default() -> Self {
+        Self {
+            network: NftMakerNetwork::default(),
+            policy_locks_at: format_lock_date(Utc::now() + Duration::days(365)),
+            max_supply: default_max_supply(),
+            payout_wallet: None,
+            description: None,
+            project_url: None,
+            tokenname_prefix: None,
+        }
+    }
+}
+
+impl NftMakerProjectConfig {
+    /// Fill in everything that wasn't passed to `pix new`, taking the lock date from `script`
+    pub fn prompt(args: &NewArgs, script: Option<&NativeScript>) -> anyhow::Result<Self> {
+        let defaults = Self::default();
+
+        let optional = |value: String| Some(value).filter(|value| !value.is_empty());
+
+        let network = match args.network {
+            Some(network) => network,
+            None => {
+                let items = vec![NftMakerNetwork::Testnet, NftMakerNetwork::Mainnet];
+
+                let selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("select the network")
+                    .items(&items)
+                    .default(0)
+                    .interact_on_opt(&Term::stderr())?;
+
+                selection.map_or(defaults.network, |index| items[index])
+            }
+        };
+
+        let policy_locks_at = match script {
+            // a policy brought along locks when its script says so
+            Some(script) => {
+                match policy::lock_date(script, network, args.policy_locks_at.as_deref())? {
+                    Some(lock) => format_lock_date(lock),
+                    None if args.policy_locks_at.is_some() => {
+                        return Err(anyhow!(
+                            "the policy script never locks, leave out --policy-locks-at"
+                        ))
+                    }
+                    None => defaults.policy_locks_at,
+                }
+            }
+            None => {
+                let date = match &args.policy_locks_at {
+                    Some(date) => date.clone(),
+                    None => Input::new()
+                        .with_prompt("enter the policy lock date")
+                        .default(defaults.policy_locks_at)
+                        .interact_text()?,
+                };
+
+                format_lock_date(parse_lock_date(&date)?)
+            }
+        };
+
+        let max_supply = match args.max_supply {
+            Some(max_supply) => max_supply,
+            None => Input::new()
+                .with_prompt("enter the max supply per token")
+                .default(defaults.max_supply)
+                .interact_text()?,
+        };
+
+        if max_supply < 1 {
+            return Err(anyhow!("max supply must be at least 1"));
+        }
+
+        let text = |flag: &Option<String>, prompt: &str| -> anyhow::Result<Option<String>> {
+            match flag {
+                Some(value) => Ok(optional(value.clone())),
+                None => Ok(optional(
+                    Input::new()
+                        .with_prompt(prompt)
+                        .allow_empty(true)
+                        .interact_text()?,
+                )),
+            }
+        };
+
+        Ok(Self {
+            network,
+            policy_locks_at,
+            max_supply,
+            payout_wallet: text(&args.payout_wallet, "enter the payout wallet address")?,
+            description: text(&args.description, "enter the project description")?,
+            project_url: text(&args.project_url, "enter the project url")?,
+            tokenname_prefix: text(&args.tokenname_prefix, "enter the token name prefix")?,
+        })
+    }
+}
+
+/// Parse a policy lock date given as `2027-01-31` (midnight UTC) or an RFC 3339 date time
+pub fn parse_lock_date(date: &str) -> anyhow::Result<DateTime<Utc>> {
+    let date = date.trim();
+
+    let parsed = match DateTime::parse_from_rfc3339(date) {
+        Ok(date_time) => date_time.with_timezone(&Utc),
+        Err(_) => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(|day| Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).unwrap()))
+            .with_context(|| {
+                format!(
+                    "invalid policy lock date `{}`, use 2027-01-31 or 2027-01-31T12:00:00Z",
+                    date
+                )
+            })?,
+    };
+
+    if parsed <= Utc::now() {
+        return Err(anyhow!("policy lock date {} is in the past", date));
+    }
+
+    Ok(parsed)
+}
+
+pub fn format_lock_date(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Extra files uploaded with each NFT, found next to its image
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Default, Clone)]
+pub struct SubfileConfig {
+    /// Appended to the token's name, `_hires.png` picks up `Name#1/Name#1_hires.png`
+    pub suffix: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mimetype: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_placeholder: Option<Map<String, Value>>,
+    /// Where the files are kept, relative to the config, as `pix gen` clears the token folders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folder: Option<PathBuf>,
+}
+
+impl SubfileConfig {
+    /// The file to upload with the token in `token_folder`
+    pub fn path(&self, config_path: &Path, token_folder: &Path) -> PathBuf {
+        let token_name = token_folder
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+
+        let file_name = format!("{}{}", token_name, self.suffix);
+
+        match &self.folder {
+            Some(folder) => config_path
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(folder)
+                .join(file_name),
+            None => token_folder.join(file_name),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone)]
+pub struct RetryConfig {
+    #[serde(default = "RetryConfig::default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "RetryConfig::default_base_delay_ms")]
+    pub base_delay_ms: u64,
+    #[serde(default = "RetryConfig::default_max_delay_ms")]
+    pub max_delay_ms: u64,
+    #[serde(default = "RetryConfig::default_jitter")]
+    pub jitter: bool,
+}
+
+impl RetryConfig {
+    fn default_max_attempts() -> u32 {
+        5
+    }
+
+    fn default_base_delay_ms() -> u64 {
+        500
+    }
+
+    fn default_max_delay_ms() -> u64 {
+        30_000
+    }
+
+    fn default_jitter() -> bool {
+        true
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: Self::default_max_attempts(),
+            base_delay_ms: Self::default_base_delay_ms(),
+            max_delay_ms: Self::default_max_delay_ms(),
+            jitter: Self::default_jitter(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -69,6 +446,20 @@ pub enum NftProjectId {
     Id(i32),
 }
 
+impl JsonSchema for NftProjectId {
+    fn schema_name() -> String {
+        String::from("NftProjectId")
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(vec![InstanceType::Integer, InstanceType::String].into()),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
 impl Serialize for NftProjectId {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -115,13 +506,22 @@ impl std::fmt::Display for NftProjectId {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Copy, Clone)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Copy, Clone, ArgEnum)]
 #[serde(rename_all = "lowercase")]
 pub enum NftMakerNetwork {
     Testnet,
     Mainnet,
 }
 
+impl Display for NftMakerNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Testnet => write!(f, "testnet"),
+            Self::Mainnet => write!(f, "mainnet"),
+        }
+    }
+}
+
 impl NftMakerNetwork {
     pub fn is_testnet(&self) -> bool {
         matches!(self, Self::Testnet)
@@ -132,6 +532,7 @@ impl NftMakerNetwork {
     }
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for NftMakerNetwork {
     fn default() -> Self {
         Self::Testnet
@@ -140,23 +541,37 @@ impl Default for NftMakerNetwork {
 
 impl AppConfig {
     pub fn new(file_name: &str) -> anyhow::Result<Self> {
-        let mut s = Config::default();
+        // read by hand rather than through the config crate, which loses the order of maps
+        let (_, _, map) = ConfigFormat::read(Path::new(file_name))?;
 
-        let (_, global_path) = get_global_config_paths()?;
+        let value = Value::Object(map);
 
-        let global_file_name = global_path
-            .to_str()
-            .context("failed to load global config")?;
+        schema::validate(&value).with_context(|| format!("invalid config {}", file_name))?;
 
-        s.merge(File::with_name(global_file_name).required(false))?;
+        let mut config: Self = serde_json::from_value(value)
+            .map_err(|e| anyhow!("loading config\nReason: {}", e.to_string()))?;
 
-        s.merge(File::with_name(file_name).required(true))?;
+        if let Some(nft_maker) = &mut config.nft_maker {
+            // a key in the environment beats one in the project
+            if let Some(apikey) = env_apikey() {
+                nft_maker.apikey = apikey;
+            } else if nft_maker.apikey.is_empty() {
+                // most commands never call the API, the client reports a missing key when built
+                nft_maker.apikey = GlobalConfig::new()
+                    .and_then(|global| global.apikey(config.profile.as_deref()))
+                    .ok()
+                    .flatten()
+                    .unwrap_or_default();
 
-        s.try_into()
-            .map_err(|e| anyhow!("loading config\nReason: {}", e.to_string()))
+                nft_maker.profile = config.profile.clone();
+            }
+        }
+
+        Ok(config)
     }
 
-    pub fn prompt() -> anyhow::Result<Self> {
+    /// Ask for the project's settings, offering `policy_id` when the policy is already known
+    pub fn prompt(policy_id: Option<String>) -> anyhow::Result<Self> {
         let name: String = Input::new()
             .with_prompt("enter asset name")
             .allow_empty(false)
@@ -173,9 +588,22 @@ impl AppConfig {
             None
         };
 
-        let policy_id: String = Input::new()
+        let mut input = Input::new();
+
+        if let Some(policy_id) = policy_id {
+            input.default(policy_id);
+        }
+
+        let policy_id: String = input
             .with_prompt("enter policy id")
             .allow_empty(false)
+            .validate_with(|id: &String| {
+                if policy::is_hash(id) {
+                    Ok(())
+                } else {
+                    Err("a policy id is 56 hex characters")
+                }
+            })
             .interact_text()?;
 
         let policy_id = if !policy_id.is_empty() {
@@ -243,12 +671,14 @@ impl AppConfig {
                     none: None,
                     exclude_if_sets: None,
                     exclude_if_traits: None,
+                    group: None,
                 });
             }
         }
 
         Ok(Self {
             policy_id,
+            policy_script: None,
             name,
             display_name,
             mode,
@@ -257,11 +687,363 @@ impl AppConfig {
             tolerance: 50,
             path: "images".into(),
             sets: None,
+            set_attribute: None,
             layers,
+            attributes: None,
             nft_maker: None,
+            nft_maker_project: None,
+            metadata: None,
+            metaplex: None,
+            profile: None,
             extra: Some(extra),
         })
     }
+
+    pub fn metadata_format(&self) -> MetadataFormat {
+        self.metadata
+            .as_ref()
+            .map_or_else(MetadataFormat::default, |metadata| metadata.format)
+    }
+
+    /// The asset name a token ends up with on chain, including NFT Maker's token name prefix
+    pub fn asset_name(&self, number: usize) -> String {
+        let prefix = self
+            .nft_maker_project
+            .as_ref()
+            .and_then(|project| project.tokenname_prefix.as_deref())
+            .unwrap_or_default();
+
+        format!("{}{}{}", prefix, self.name, number)
+    }
+
+    /// The token number of an asset name, with or without NFT Maker's token name prefix
+    pub fn token_number(&self, asset_name: &str) -> Option<usize> {
+        let prefix = self
+            .nft_maker_project
+            .as_ref()
+            .and_then(|project| project.tokenname_prefix.as_deref())
+            .unwrap_or_default();
+
+        asset_name
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_prefix(&self.name))
+            .or_else(|| asset_name.strip_prefix(&self.name))?
+            .parse()
+            .ok()
+    }
+
+    /// The set called `name`, if the project has sets
+    pub fn set(&self, name: &str) -> Option<&SetConfig> {
+        self.sets.iter().flatten().find(|set| set.name == name)
+    }
+
+    /// The name tokens are displayed under, the set's own when it has one
+    pub fn display_name<'a>(&'a self, set: Option<&'a SetConfig>) -> &'a str {
+        set.and_then(|set| set.display_name.as_ref())
+            .or(self.display_name.as_ref())
+            .unwrap_or(&self.name)
+    }
+
+    /// Metaplex candy machines expect assets numbered from 0 regardless of `start_at_one`
+    pub fn starts_at_one(&self) -> bool {
+        self.start_at_one && self.metadata_format() != MetadataFormat::Metaplex
+    }
+
+    /// The name a token's set is recorded under in rarity.json, `set_attribute` or `set`
+    pub fn set_key(&self) -> Option<&str> {
+        self.sets.as_ref()?;
+
+        Some(self.set_attribute.as_deref().unwrap_or("set"))
+    }
+
+    pub fn validate_sets(&self) -> anyhow::Result<()> {
+        if self.set_attribute.is_some() && self.sets.is_none() {
+            return Err(anyhow!("set_attribute needs sets"));
+        }
+
+        if let Some(key) = self.set_key() {
+            let clash = |what: &str| {
+                match &self.set_attribute {
+                Some(_) => anyhow!("set_attribute {} is already the name of {}", key, what),
+                None => anyhow!(
+                    "sets are recorded as {}, which is already the name of {}, pick another name with set_attribute",
+                    key,
+                    what
+                ),
+            }
+            };
+
+            let layers = self
+                .sets
+                .iter()
+                .flatten()
+                .flat_map(|set| set.layers(&self.layers))
+                .map(|layer| layer.display_name.unwrap_or(layer.name));
+
+            let attributes = self
+                .attributes
+                .iter()
+                .flatten()
+                .map(|attribute| attribute.name.clone());
+
+            if layers.chain(attributes).any(|name| name == key) {
+                return Err(clash("a layer or attribute"));
+            }
+
+            // grouped attributes nest under the first part of their group
+            let layer_groups = self
+                .sets
+                .iter()
+                .flatten()
+                .flat_map(|set| set.layers(&self.layers))
+                .filter_map(|layer| layer.group);
+
+            let attribute_groups = self
+                .attributes
+                .iter()
+                .flatten()
+                .filter_map(|attribute| attribute.group.clone());
+
+            if layer_groups
+                .chain(attribute_groups)
+                .any(|group| group.split('.').next() == Some(key))
+            {
+                return Err(clash("an attribute group"));
+            }
+        }
+
+        let mut names = Vec::new();
+
+        for set in self.sets.iter().flatten() {
+            if names.contains(&&set.name) {
+                return Err(anyhow!("set {} is defined more than once", set.name));
+            }
+
+            names.push(&set.name);
+
+            let layers = set.layers(&self.layers);
+
+            for layer in set.none.iter().flat_map(|none| none.keys()) {
+                if !layers.iter().any(|other| &other.name == layer) {
+                    return Err(anyhow!(
+                        "set {} overrides none for unknown layer {}",
+                        set.name,
+                        layer
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn validate_attributes(&self) -> anyhow::Result<()> {
+        for attribute in self.attributes.iter().flatten() {
+            if attribute.min > attribute.max {
+                return Err(anyhow!(
+                    "attribute {} has a min greater than its max",
+                    attribute.name
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check `policy_id` is well formed and matches `policy_script`, returning the script
+    ///
+    /// A relative `policy_script` is relative to the folder of the config at `config_path`.
+    pub fn validate_policy(&self, config_path: &Path) -> anyhow::Result<Option<NativeScript>> {
+        if let Some(policy_id) = &self.policy_id {
+            if !policy::is_hash(policy_id) {
+                return Err(anyhow!(
+                    "policy_id {} is malformed, it should be 56 hex characters",
+                    policy_id
+                ));
+            }
+        }
+
+        match &self.policy_script {
+            Some(path) => {
+                let path = config_path
+                    .parent()
+                    .unwrap_or_else(|| Path::new(""))
+                    .join(path);
+
+                let script = NativeScript::load(&path)?;
+
+                policy::check_policy_id(&script, &path, self.policy_id.as_deref())?;
+
+                Ok(Some(script))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn validate_metaplex(&self) -> anyhow::Result<()> {
+        if self.metadata_format() != MetadataFormat::Metaplex {
+            return Ok(());
+        }
+
+        let metaplex = self
+            .metaplex
+            .as_ref()
+            .context("metadata format metaplex requires a metaplex config")?;
+
+        if metaplex.seller_fee_basis_points > 10000 {
+            return Err(anyhow!("seller_fee_basis_points must be at most 10000"));
+        }
+
+        let shares = metaplex
+            .creators
+            .iter()
+            .fold(0, |acc, creator| acc + creator.share as u32);
+
+        if !metaplex.creators.is_empty() && shares != 100 {
+            return Err(anyhow!("creator shares must add up to 100"));
+        }
+
+        Ok(())
+    }
+}
+
+/// The file names a project config is looked for under, in order
+pub const CONFIG_FILES: [&str; 4] = ["pix.json", "pix.yaml", "pix.yml", "pix.toml"];
+
+/// The project config in the current directory, `pix.json` when there is none
+pub fn find_config() -> &'static str {
+    CONFIG_FILES
+        .into_iter()
+        .find(|file| Path::new(file).is_file())
+        .unwrap_or(CONFIG_FILES[0])
+}
+
+/// A format project configs can be written in
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, ArgEnum)]
+pub enum ConfigFormat {
+    #[default]
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(Self::Json),
+            Some("yaml" | "yml") => Ok(Self::Yaml),
+            Some("toml") => Ok(Self::Toml),
+            _ => Err(anyhow!(
+                "{} is not a .json, .yaml or .toml file",
+                path.display()
+            )),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+            Self::Toml => "toml",
+        }
+    }
+
+    pub fn parse(&self, contents: &str) -> anyhow::Result<Map<String, Value>> {
+        let map = match self {
+            Self::Json => serde_json::from_str(contents)?,
+            Self::Yaml => serde_yaml::from_str(contents)?,
+            Self::Toml => toml::from_str(contents)?,
+        };
+
+        Ok(map)
+    }
+
+    /// Read a config file in the format its extension says
+    pub fn read(path: &Path) -> anyhow::Result<(Self, String, Map<String, Value>)> {
+        let format = Self::from_path(path)?;
+
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+
+        let map = format
+            .parse(&contents)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+
+        Ok((format, contents, map))
+    }
+
+    /// Add a top level `key` to a config file's `contents`
+    ///
+    /// YAML and TOML get it appended, keeping the comments a rewrite would drop.
+    pub fn insert(&self, contents: &str, key: &str, value: Value) -> anyhow::Result<String> {
+        let mut map = Map::new();
+
+        map.insert(key.to_owned(), value);
+
+        match self {
+            Self::Json => {
+                let mut config = self.parse(contents)?;
+
+                config.extend(map);
+
+                self.to_string(&config)
+            }
+            Self::Yaml | Self::Toml => Ok(format!(
+                "{}\n{}",
+                contents.trim_end(),
+                self.to_string(&map)?
+            )),
+        }
+    }
+
+    pub fn to_string<T: Serialize>(&self, value: &T) -> anyhow::Result<String> {
+        let mut value = serde_json::to_value(value)?;
+
+        let contents = match self {
+            Self::Json => serde_json::to_string_pretty(&value)?,
+            Self::Yaml => {
+                let contents = serde_yaml::to_string(&value)?;
+
+                contents
+                    .strip_prefix("---\n")
+                    .map(String::from)
+                    .unwrap_or(contents)
+            }
+            Self::Toml => {
+                // toml has no null, leaving a key out means the same
+                strip_nulls(&mut value);
+                strip_array_nulls(&mut value);
+
+                toml::to_string_pretty(&toml::Value::try_from(value)?)?
+            }
+        };
+
+        Ok(contents)
+    }
+}
+
+/// Drop every object member that is `null`, which serde reads the same as a missing field
+pub fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        Value::Array(values) => values.iter_mut().for_each(strip_nulls),
+        _ => (),
+    }
+}
+
+/// Drop `null` from arrays too, as toml can't write them at all
+fn strip_array_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => map.values_mut().for_each(strip_array_nulls),
+        Value::Array(values) => {
+            values.retain(|value| !value.is_null());
+            values.iter_mut().for_each(strip_array_nulls);
+        }
+        _ => (),
+    }
 }
 
 pub fn get_global_config_paths() -> anyhow::Result<(PathBuf, PathBuf)> {
@@ -291,14 +1073,26 @@ pub fn create_global_config_paths() -> anyhow::Result<(PathBuf, PathBuf)> {
     Ok((global_config_dir, global_config_file))
 }
 
+/// Overrides every stored API key, for CI or to keep keys off disk
+pub const APIKEY_ENV: &str = "PIX_NFT_MAKER_APIKEY";
+
+fn env_apikey() -> Option<String> {
+    env::var(APIKEY_ENV)
+        .ok()
+        .filter(|apikey| !apikey.is_empty())
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct NftMakerGlobalConfig {
     pub apikey: String,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Default)]
 pub struct GlobalConfig {
     pub nft_maker: Option<NftMakerGlobalConfig>,
+    /// Named API keys, picked with `profile` in the project config
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, NftMakerGlobalConfig>,
 }
 
 impl GlobalConfig {
@@ -316,4 +1110,25 @@ impl GlobalConfig {
         s.try_into()
             .map_err(|e| anyhow!("loading config\nReason: {}", e.to_string()))
     }
+
+    /// The API key to use, from `PIX_NFT_MAKER_APIKEY`, the profile or the default one
+    pub fn apikey(&self, profile: Option<&str>) -> anyhow::Result<Option<String>> {
+        if let Some(apikey) = env_apikey() {
+            return Ok(Some(apikey));
+        }
+
+        match profile {
+            Some(profile) => self
+                .profiles
+                .get(profile)
+                .map(|config| Some(config.apikey.clone()))
+                .with_context(|| {
+                    format!(
+                        "no profile named {}, add it with pix auth --profile {}",
+                        profile, profile
+                    )
+                }),
+            None => Ok(self.nft_maker.as_ref().map(|config| config.apikey.clone())),
+        }
+    }
 }
@@ -0,0 +1,586 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::Mode,
+    config::{AppConfig, LayerConfig, MetadataConfig, SetConfig},
+    layers::NONE,
+};
+
+/// One entry of HashLips' `layerConfigurations`
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerConfiguration {
+    pub grow_edition_size_to: usize,
+    pub layers_order: Vec<HashLipsLayer>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HashLipsLayer {
+    pub name: String,
+    #[serde(default)]
+    pub options: HashLipsLayerOptions,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HashLipsLayerOptions {
+    pub display_name: Option<String>,
+    /// Options pix has nothing for, like `blend`, `opacity` and `bypassDNA`
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl HashLipsLayerOptions {
+    /// The options that change how HashLips draws the layer but are lost in pix
+    pub fn ignored(&self) -> Vec<&str> {
+        self.other
+            .iter()
+            .filter(|(name, value)| match (name.as_str(), value) {
+                ("blend", Value::String(mode)) => {
+                    !matches!(mode.as_str(), "MODE.sourceOver" | "source-over")
+                }
+                ("opacity", value) => value.as_f64() != Some(1.0),
+                ("bypassDNA", value) => value.as_bool() != Some(false),
+                _ => true,
+            })
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+}
+
+/// The parts of a HashLips `config.js` pix understands
+#[derive(Debug)]
+pub struct HashLipsConfig {
+    pub layer_configurations: Vec<LayerConfiguration>,
+    pub name_prefix: Option<String>,
+    pub description: Option<String>,
+    pub unique_dna_torrance: Option<usize>,
+    pub extra_metadata: Option<Map<String, Value>>,
+}
+
+impl HashLipsConfig {
+    /// Read `src/config.js`, or `config.js`, of a HashLips project
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let path = [dir.join("src").join("config.js"), dir.join("config.js")]
+            .into_iter()
+            .find(|path| path.is_file())
+            .with_context(|| format!("could not find config.js in {}", dir.display()))?;
+
+        let source = fs::read_to_string(&path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+
+        Self::parse(&source).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let layer_configurations =
+            assignment(source, "layerConfigurations")?.context("no layerConfigurations found")?;
+
+        let layer_configurations: Vec<LayerConfiguration> =
+            serde_json::from_value(layer_configurations)
+                .context("layerConfigurations is not a list of layer configurations")?;
+
+        if layer_configurations.is_empty() {
+            return Err(anyhow!("layerConfigurations is empty"));
+        }
+
+        // the optional settings are left out when they aren't plain values
+        let optional = |name: &str| assignment(source, name).ok().flatten();
+
+        Ok(Self {
+            layer_configurations,
+            name_prefix: optional("namePrefix").and_then(|value| Some(value.as_str()?.to_owned())),
+            description: optional("description").and_then(|value| Some(value.as_str()?.to_owned())),
+            unique_dna_torrance: optional("uniqueDnaTorrance")
+                .and_then(|value| Some(value.as_u64()? as usize)),
+            extra_metadata: optional("extraMetadata")
+                .and_then(|value| Some(value.as_object()?.clone()))
+                .filter(|extra| !extra.is_empty()),
+        })
+    }
+}
+
+/// Turn the HashLips project in `dir` into a pix project in `output`
+///
+/// Every layer is copied into `output/images`, one folder per set when there is more
+/// than one layer configuration, and trait files without a `#weight` get HashLips'
+/// default weight of 1. `None` traits become `none` weights. Comes back with warnings
+/// about what pix can't carry over.
+pub fn import(dir: &Path, output: &Path) -> anyhow::Result<(AppConfig, Vec<String>)> {
+    let hashlips = HashLipsConfig::load(dir)?;
+
+    let mut warnings = Vec::new();
+
+    for (index, configuration) in hashlips.layer_configurations.iter().enumerate() {
+        for layer in &configuration.layers_order {
+            let ignored = layer.options.ignored();
+
+            if !ignored.is_empty() {
+                warnings.push(format!(
+                    "layer {} of configuration {} uses {}, which pix doesn't support, so it was left out",
+                    layer.name,
+                    index + 1,
+                    ignored.join(", ")
+                ));
+            }
+        }
+    }
+
+    let layers_dir = dir.join("layers");
+
+    let images = output.join("images");
+
+    if images.exists() {
+        return Err(anyhow!("{} already exists", images.display()));
+    }
+
+    let mut configurations: Vec<Vec<LayerConfig>> = hashlips
+        .layer_configurations
+        .iter()
+        .map(|configuration| {
+            configuration
+                .layers_order
+                .iter()
+                .map(|layer| LayerConfig {
+                    name: layer.name.clone(),
+                    display_name: layer.options.display_name.clone(),
+                    ..Default::default()
+                })
+                .collect()
+        })
+        .collect();
+
+    // nothing is copied until every file made it through
+    let mut folders = Vec::new();
+
+    let mut copies = Vec::new();
+
+    let sets = if configurations.len() > 1 {
+        let mut sets = Vec::new();
+
+        let mut previous = 0;
+
+        for (index, (configuration, layers)) in hashlips
+            .layer_configurations
+            .iter()
+            .zip(&configurations)
+            .enumerate()
+        {
+            // growEditionSizeTo counts from the start of the collection
+            let amount = configuration
+                .grow_edition_size_to
+                .checked_sub(previous)
+                .filter(|amount| *amount > 0)
+                .with_context(|| {
+                    format!(
+                        "growEditionSizeTo of configuration {} has to be larger than the one before it",
+                        index + 1
+                    )
+                })?;
+
+            previous = configuration.grow_edition_size_to;
+
+            let name = format!("set{}", index + 1);
+
+            let plan = plan_copies(&layers_dir, layers, &images.join(&name))?;
+
+            folders.extend(plan.folders);
+            copies.extend(plan.copies);
+
+            sets.push(SetConfig {
+                name,
+                amount,
+                layers: (!same_layers(layers, &configurations[0])).then(|| layers.clone()),
+                none: Some(plan.none).filter(|none| !none.is_empty()),
+                ..Default::default()
+            });
+        }
+
+        Some(sets)
+    } else {
+        let plan = plan_copies(&layers_dir, &configurations[0], &images)?;
+
+        for layer in &mut configurations[0] {
+            layer.none = plan.none.get(&layer.name).copied();
+        }
+
+        folders.extend(plan.folders);
+        copies.extend(plan.copies);
+
+        None
+    };
+
+    // a layer can be left with nothing but its none weight, its folder still has to be there
+    for folder in folders {
+        fs::create_dir_all(folder)?;
+    }
+
+    for (source, destination) in copies {
+        if let Some(folder) = destination.parent() {
+            fs::create_dir_all(folder)?;
+        }
+
+        fs::copy(&source, &destination)
+            .with_context(|| format!("could not copy {}", source.display()))?;
+    }
+
+    let amount = hashlips
+        .layer_configurations
+        .last()
+        .map(|configuration| configuration.grow_edition_size_to)
+        .unwrap_or_default();
+
+    let display_name = hashlips.name_prefix.clone();
+
+    let name: String = display_name
+        .iter()
+        .flat_map(|prefix| prefix.chars())
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect();
+
+    let name = if name.is_empty() {
+        output
+            .canonicalize()?
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("Collection")
+            .to_owned()
+    } else {
+        name
+    };
+
+    let config = AppConfig {
+        display_name: display_name.filter(|display_name| display_name != &name),
+        name,
+        mode: Mode::Advanced,
+        start_at_one: true,
+        amount,
+        tolerance: hashlips.unique_dna_torrance.unwrap_or(50),
+        path: PathBuf::from("images"),
+        sets,
+        layers: configurations.into_iter().next().unwrap_or_default(),
+        metadata: hashlips.description.map(|description| MetadataConfig {
+            description: Some(description),
+            ..Default::default()
+        }),
+        extra: hashlips.extra_metadata,
+        ..Default::default()
+    };
+
+    Ok((config, warnings))
+}
+
+fn same_layers(a: &[LayerConfig], b: &[LayerConfig]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| a.name == b.name && a.display_name == b.display_name)
+}
+
+/// What importing the layers of one configuration takes
+#[derive(Default)]
+struct Plan {
+    /// The folder of every layer
+    folders: Vec<PathBuf>,
+    /// Where each trait image goes
+    copies: Vec<(PathBuf, PathBuf)>,
+    /// The weight of each layer's `None` trait, which becomes the layer's `none` instead of an image
+    none: BTreeMap<String, u32>,
+}
+
+/// Where each trait image of `layers` goes in `target`, with a weight in every file name
+fn plan_copies(layers_dir: &Path, layers: &[LayerConfig], target: &Path) -> anyhow::Result<Plan> {
+    let mut plan = Plan::default();
+
+    for layer in layers {
+        let source = layers_dir.join(&layer.name);
+
+        plan.folders.push(target.join(&layer.name));
+
+        let entries = source.read_dir().with_context(|| {
+            format!(
+                "missing folder {} for layer {}",
+                source.display(),
+                layer.name
+            )
+        })?;
+
+        let mut traits: Vec<(String, PathBuf)> = Vec::new();
+
+        for entry in entries {
+            let path = entry?.path();
+
+            let file_name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .with_context(|| format!("{} is not a valid file name", path.display()))?;
+
+            // HashLips skips hidden files, like .DS_Store
+            if !path.is_file() || file_name.starts_with('.') {
+                continue;
+            }
+
+            // pix only reads .png, so other images would quietly go missing
+            let stem = match path.extension().and_then(|ext| ext.to_str()) {
+                Some(ext) if ext.eq_ignore_ascii_case("png") => {
+                    file_name[..file_name.len() - 4].to_owned()
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "{} is not a .png, pix can only use png trait images",
+                        path.display()
+                    ))
+                }
+            };
+
+            let (name, weight) =
+                weighted(&stem).with_context(|| format!("failed to import {}", path.display()))?;
+
+            if let Some((_, other)) = traits.iter().find(|(other, _)| other == &name) {
+                return Err(anyhow!(
+                    "{} and {} are both trait {} of layer {}",
+                    other.display(),
+                    path.display(),
+                    name,
+                    layer.name
+                ));
+            }
+
+            // pix reserves None for leaving the layer out, which is what HashLips' empty image does
+            if name == NONE {
+                plan.none.insert(layer.name.clone(), weight);
+            } else {
+                let destination = target
+                    .join(&layer.name)
+                    .join(format!("{}#{}.png", name, weight));
+
+                plan.copies.push((path.clone(), destination));
+            }
+
+            traits.push((name, path));
+        }
+    }
+
+    Ok(plan)
+}
+
+/// A trait's name and the weight HashLips would give it
+///
+/// Like HashLips, the name is everything before the first `#` and the weight
+/// everything after the last one.
+fn weighted(stem: &str) -> anyhow::Result<(String, u32)> {
+    let name = stem.split('#').next().unwrap_or(stem).to_owned();
+
+    match stem.rsplit_once('#') {
+        Some((_, weight)) => match weight.parse::<u32>() {
+            Ok(weight) => Ok((name, weight)),
+            Err(_) if weight.parse::<f64>().is_ok() => Err(anyhow!(
+                "weight {} is not a whole number, pix weights are",
+                weight
+            )),
+            // HashLips weighs anything that isn't a number as 1
+            Err(_) => Ok((name, 1)),
+        },
+        None => Ok((name, 1)),
+    }
+}
+
+/// The value assigned to `name` in a JavaScript source, like `const name = [...]`
+fn assignment(source: &str, name: &str) -> anyhow::Result<Option<Value>> {
+    let mut rest = source;
+
+    while let Some(start) = rest.find(name) {
+        let before = rest[..start].chars().next_back();
+
+        let after = rest[start + name.len()..].trim_start();
+
+        rest = &rest[start + name.len()..];
+
+        if before.is_some_and(is_identifier) || !after.starts_with('=') || after.starts_with("==") {
+            continue;
+        }
+
+        let mut value = &after[1..];
+
+        return parse_value(&mut value).map(Some);
+    }
+
+    Ok(None)
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$' || c == '.'
+}
+
+/// Skip whitespace and comments
+fn skip(rest: &mut &str) {
+    loop {
+        *rest = rest.trim_start();
+
+        if let Some(comment) = rest.strip_prefix("//") {
+            *rest = comment.find('\n').map_or("", |end| &comment[end..]);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            *rest = comment.find("*/").map_or("", |end| &comment[end + 2..]);
+        } else {
+            return;
+        }
+    }
+}
+
+/// Parse a JavaScript literal, other expressions like `MODE.multiply` become strings
+fn parse_value(rest: &mut &str) -> anyhow::Result<Value> {
+    skip(rest);
+
+    match rest.chars().next() {
+        Some('{') => {
+            *rest = &rest[1..];
+
+            let mut map = Map::new();
+
+            loop {
+                skip(rest);
+
+                if let Some(after) = rest.strip_prefix('}') {
+                    *rest = after;
+
+                    return Ok(Value::Object(map));
+                }
+
+                let key = match rest.chars().next() {
+                    Some('"' | '\'' | '`') => parse_string(rest)?,
+                    _ => parse_identifier(rest)?,
+                };
+
+                skip(rest);
+
+                *rest = rest
+                    .strip_prefix(':')
+                    .with_context(|| format!("expected `:` after {}", key))?;
+
+                let value = parse_value(rest)?;
+
+                map.insert(key, value);
+
+                end_item(rest, '}')?;
+            }
+        }
+        Some('[') => {
+            *rest = &rest[1..];
+
+            let mut values = Vec::new();
+
+            loop {
+                skip(rest);
+
+                if let Some(after) = rest.strip_prefix(']') {
+                    *rest = after;
+
+                    return Ok(Value::Array(values));
+                }
+
+                values.push(parse_value(rest)?);
+
+                end_item(rest, ']')?;
+            }
+        }
+        Some('"' | '\'' | '`') => Ok(Value::String(parse_string(rest)?)),
+        Some(c) if c.is_ascii_digit() || c == '-' => {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_'))
+                .unwrap_or(rest.len());
+
+            let number = rest[..end].replace('_', "");
+
+            *rest = &rest[end..];
+
+            if let Ok(number) = number.parse::<i64>() {
+                return Ok(Value::from(number));
+            }
+
+            number
+                .parse::<f64>()
+                .map(Value::from)
+                .map_err(|_| anyhow!("{} is not a number", number))
+        }
+        Some(_) => {
+            let identifier = parse_identifier(rest)?;
+
+            Ok(match identifier.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" | "undefined" => Value::Null,
+                _ => Value::String(identifier),
+            })
+        }
+        None => Err(anyhow!("unexpected end of file")),
+    }
+}
+
+/// Skip the `,` after an item, or stop before the closing bracket
+fn end_item(rest: &mut &str, close: char) -> anyhow::Result<()> {
+    skip(rest);
+
+    if let Some(after) = rest.strip_prefix(',') {
+        *rest = after;
+    } else if !rest.starts_with(close) {
+        return Err(anyhow!(
+            "expected `,` or `{}` but found `{}`",
+            close,
+            rest.chars().take(20).collect::<String>()
+        ));
+    }
+
+    Ok(())
+}
+
+fn parse_identifier(rest: &mut &str) -> anyhow::Result<String> {
+    let end = rest.find(|c: char| !is_identifier(c)).unwrap_or(rest.len());
+
+    if end == 0 {
+        return Err(anyhow!(
+            "unexpected `{}`",
+            rest.chars().take(20).collect::<String>()
+        ));
+    }
+
+    let identifier = rest[..end].to_owned();
+
+    *rest = &rest[end..];
+
+    Ok(identifier)
+}
+
+fn parse_string(rest: &mut &str) -> anyhow::Result<String> {
+    let mut chars = rest.char_indices();
+
+    let quote = chars.next().map(|(_, c)| c).context("expected a string")?;
+
+    let mut string = String::new();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, 'n')) => string.push('\n'),
+                Some((_, 't')) => string.push('\t'),
+                Some((_, c)) => string.push(c),
+                None => break,
+            },
+            c if c == quote => {
+                *rest = &rest[index + 1..];
+
+                return Ok(string);
+            }
+            c => string.push(c),
+        }
+    }
+
+    Err(anyhow!("unterminated string"))
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::nft_maker::UploadNftResponse;
+
+pub const JOURNAL: &str = "upload_journal.jsonl";
+
+/// Keeps track of what has been uploaded to NFT Maker so uploads can be resumed
+///
+/// The journal is a JSON lines file: a header naming the project, then one line per outcome,
+/// appended as it happens. Later lines for a token win.
+#[derive(Debug, Default)]
+pub struct Journal {
+    path: PathBuf,
+    pub nft_project_id: String,
+    pub tokens: BTreeMap<usize, JournalEntry>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct Header {
+    nft_project_id: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Line {
+    number: usize,
+    #[serde(flatten)]
+    entry: JournalEntry,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum JournalEntry {
+    Uploaded {
+        nft_id: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nft_uid: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ipfs_hash: Option<String>,
+        /// The hash of the image that was uploaded, as the manifest records it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image_hash: Option<String>,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+impl JournalEntry {
+    pub fn is_uploaded(&self) -> bool {
+        matches!(self, Self::Uploaded { .. })
+    }
+
+    /// A successful upload of an image with the given hash
+    pub fn uploaded(response: &UploadNftResponse, image_hash: String) -> Self {
+        Self::Uploaded {
+            nft_id: response.nft_id,
+            nft_uid: response.nft_uid.clone(),
+            ipfs_hash: response.ipfs_hash_mainnft.clone(),
+            image_hash: Some(image_hash),
+        }
+    }
+}
+
+impl Journal {
+    /// Load the journal that lives next to the project config, or start a new one
+    pub fn load(config_path: &str, nft_project_id: String) -> anyhow::Result<Self> {
+        let path = Path::new(config_path)
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(JOURNAL);
+
+        if !path.exists() {
+            return Ok(Self {
+                path,
+                nft_project_id,
+                tokens: BTreeMap::new(),
+            });
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("could not open {}", path.display()))?;
+
+        let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+
+        let header: Header = lines
+            .next()
+            .map(serde_json::from_str)
+            .transpose()
+            .with_context(|| format!("could not parse {}", path.display()))?
+            .unwrap_or_default();
+
+        if header.nft_project_id != nft_project_id {
+            return Err(anyhow!(
+                "{} belongs to project {}, move it out of the way to upload to {}",
+                path.display(),
+                header.nft_project_id,
+                nft_project_id
+            ));
+        }
+
+        let mut tokens = BTreeMap::new();
+
+        let mut torn = false;
+
+        let mut lines = lines.peekable();
+
+        while let Some(line) = lines.next() {
+            match serde_json::from_str::<Line>(line) {
+                Ok(line) => {
+                    tokens.insert(line.number, line.entry);
+                }
+                // a crash halfway through the last write only loses that one line
+                Err(_) if lines.peek().is_none() => torn = true,
+                Err(e) => {
+                    return Err(e).with_context(|| format!("could not parse {}", path.display()))
+                }
+            }
+        }
+
+        let journal = Self {
+            path,
+            nft_project_id,
+            tokens,
+        };
+
+        // drop the torn line, or the next record would be appended onto it
+        if torn {
+            journal.save()?;
+        }
+
+        Ok(journal)
+    }
+
+    pub fn get(&self, number: usize) -> Option<&JournalEntry> {
+        self.tokens.get(&number)
+    }
+
+    /// Record the outcome for a token, appending it to the journal straight away
+    pub fn record(&mut self, number: usize, entry: JournalEntry) -> anyhow::Result<()> {
+        if !self.path.exists() {
+            self.tokens.insert(number, entry);
+
+            return self.save();
+        }
+
+        let line = Line { number, entry };
+
+        let mut contents = serde_json::to_string(&line)?;
+
+        contents.push('\n');
+
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .with_context(|| format!("could not write {}", self.path.display()))?;
+
+        self.tokens.insert(line.number, line.entry);
+
+        Ok(())
+    }
+
+    /// Forget a token, so the next upload sends it again
+    pub fn remove(&mut self, number: usize) -> anyhow::Result<()> {
+        if self.tokens.remove(&number).is_some() {
+            self.save()?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the whole journal, leaving a single line per token
+    pub fn save(&self) -> anyhow::Result<()> {
+        let header = Header {
+            nft_project_id: self.nft_project_id.clone(),
+        };
+
+        let mut contents = serde_json::to_string(&header)?;
+
+        contents.push('\n');
+
+        for (number, entry) in &self.tokens {
+            let line = Line {
+                number: *number,
+                entry: entry.clone(),
+            };
+
+            contents.push_str(&serde_json::to_string(&line)?);
+            contents.push('\n');
+        }
+
+        // write to a temporary file first so a crash never leaves a truncated journal
+        let tmp = self.path.with_extension("jsonl.tmp");
+
+        fs::write(&tmp, contents)?;
+
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("could not write {}", self.path.display()))?;
+
+        Ok(())
+    }
+}
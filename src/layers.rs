@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Context};
 use image::{DynamicImage, GenericImageView};
@@ -6,7 +6,10 @@ use rand::Rng;
 
 use crate::{cli::Mode, config::LayerConfig};
 
-const RARITIES: [&str; 5] = ["common", "uncommon", "rare", "mythical", "legendary"];
+/// The trait of layers that can be left out, reserved so it can't clash with a real trait
+pub const NONE: &str = "None";
+
+pub const RARITIES: [&str; 5] = ["common", "uncommon", "rare", "mythical", "legendary"];
 
 #[derive(Debug, Clone)]
 pub struct Trait {
@@ -19,6 +22,10 @@ pub struct Trait {
 #[derive(Default)]
 pub struct Layers {
     pub data: Vec<Vec<Trait>>,
+    /// The config of each layer in `data`
+    pub configs: Vec<LayerConfig>,
+    /// The attribute group of each layer in `data`
+    pub groups: Vec<Option<String>>,
     pub width: u32,
     pub height: u32,
 }
@@ -32,12 +39,19 @@ impl Layers {
     ) -> anyhow::Result<()> {
         let mut data = Vec::new();
 
-        let layer_paths = layers
-            .iter()
-            .map(|layer| (layer, path.join(layer.name.clone())))
-            .filter(|(_, path)| path.is_dir());
+        let mut groups = Vec::new();
+
+        for layer_config in layers {
+            let layer_path = path.join(&layer_config.name);
+
+            if !layer_path.is_dir() {
+                return Err(anyhow!(
+                    "missing folder {} for layer {}",
+                    layer_path.display(),
+                    layer_config.name
+                ));
+            }
 
-        for (layer_config, layer_path) in layer_paths {
             let mut trait_list = Vec::new();
 
             let layer_name = layer_config
@@ -48,10 +62,8 @@ impl Layers {
 
             match mode {
                 Mode::Advanced => {
-                    let trait_paths = layer_path
-                        .read_dir()
-                        .with_context(|| format!("{} is not a folder", layer_path.display()))?
-                        .map(|dir| dir.unwrap().path())
+                    let trait_paths = entries(&layer_path)?
+                        .into_iter()
                         .filter(|path| path.is_file())
                         .filter(|path| matches!(path.extension(), Some(ext) if ext == "png"));
 
@@ -74,31 +86,20 @@ impl Layers {
                             .unwrap()
                             .to_string();
 
-                        if file_name.contains('#') {
-                            let parts: Vec<&str> = file_name.split('#').collect();
-
-                            let name = parts[0];
-
-                            let weight = parts[1].parse().with_context(|| {
-                                format!("{} is not a parsable number", parts[1])
-                            })?;
+                        let (name, weight) = parse_trait_file_name(&file_name)
+                            .with_context(|| format!("failed to load {}", trait_path.display()))?;
 
-                            trait_list.push(Trait {
-                                layer: layer_name.clone(),
-                                name: name.to_owned(),
-                                image: Some(image),
-                                weight,
-                            })
-                        } else {
-                            return Err(anyhow!("{} is missing `#weight`", file_name));
-                        }
+                        trait_list.push(Trait {
+                            layer: layer_name.clone(),
+                            name: name.to_owned(),
+                            image: Some(image),
+                            weight,
+                        })
                     }
                 }
                 Mode::Simple => {
-                    let rarity_paths = layer_path
-                        .read_dir()
-                        .with_context(|| format!("{} is not a folder", layer_path.display()))?
-                        .map(|dir| dir.unwrap().path())
+                    let rarity_paths = entries(&layer_path)?
+                        .into_iter()
                         .filter(|path| path.is_dir())
                         .filter(|path| RARITIES.iter().any(|rarity| path.ends_with(rarity)));
 
@@ -110,10 +111,8 @@ impl Layers {
                             })?
                             .to_str();
 
-                        let trait_paths = rarity_path
-                            .read_dir()
-                            .with_context(|| format!("{} is not a folder", rarity_path.display()))?
-                            .map(|dir| dir.unwrap().path())
+                        let trait_paths = entries(&rarity_path)?
+                            .into_iter()
                             .filter(|path| path.is_file())
                             .filter(|path| matches!(path.extension(), Some(ext) if ext == "png"));
 
@@ -159,7 +158,7 @@ impl Layers {
             if let Some(weight) = layer_config.none {
                 trait_list.push(Trait {
                     layer: layer_name.clone(),
-                    name: "None".to_string(),
+                    name: NONE.to_string(),
                     weight,
                     image: None,
                 });
@@ -173,26 +172,30 @@ impl Layers {
             {
                 trait_list.push(Trait {
                     layer: layer_name,
-                    name: "None".to_string(),
+                    name: NONE.to_string(),
                     weight: 0,
                     image: None,
                 });
             }
 
             data.push(trait_list);
+
+            groups.push(layer_config.group.clone());
         }
 
         self.data = data;
 
+        self.configs = layers.to_vec();
+
+        self.groups = groups;
+
         Ok(())
     }
 
-    pub fn create_unique(&self, layers: &[LayerConfig], set_name: &str) -> Vec<usize> {
+    pub fn create_unique(&self, set_name: &str, rng: &mut impl Rng) -> Vec<usize> {
         let mut random = Vec::new();
 
-        let mut rng = rand::thread_rng();
-
-        for (trait_list, layer_config) in self.data.iter().zip(layers) {
+        for (trait_list, layer_config) in self.data.iter().zip(&self.configs) {
             if let Some(exclude_if_sets) = &layer_config.exclude_if_sets {
                 if exclude_if_sets.iter().any(|s| s == set_name) {
                     random.push(trait_list.len() - 1);
@@ -239,3 +242,32 @@ impl Layers {
         random
     }
 }
+
+/// The trait name and weight of an advanced mode file name without its extension, like `laser#5`
+pub fn parse_trait_file_name(file_name: &str) -> anyhow::Result<(&str, u32)> {
+    let mut parts = file_name.split('#');
+
+    let name = parts.next().unwrap_or_default();
+
+    let weight = parts.next().context("missing `#weight`")?;
+
+    let weight = weight
+        .parse()
+        .with_context(|| format!("{} is not a parsable number", weight))?;
+
+    Ok((name, weight))
+}
+
+/// The entries of a folder sorted by path, so the same seed picks the same traits on every machine
+fn entries(folder: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = folder
+        .read_dir()
+        .with_context(|| format!("{} is not a folder", folder.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to read {}", folder.display()))?;
+
+    paths.sort();
+
+    Ok(paths)
+}
@@ -1,7 +1,16 @@
+pub mod check;
 pub mod cli;
 pub mod config;
+pub mod hashlips;
+pub mod journal;
 pub mod layers;
+pub mod manifest;
 pub mod metadata;
 pub mod nft_maker;
+pub mod output;
+pub mod policy;
 pub mod rarity;
+pub mod remote;
+pub mod schema;
+pub mod template;
 pub mod utils;
@@ -1,23 +1,41 @@
-use std::{collections::HashSet, fs, path::Path, process, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    process,
+    sync::Mutex,
+};
 
 use anyhow::{anyhow, Context};
 use dialoguer::{theme::ColorfulTheme, Confirm, Password};
 use image::RgbaImage;
 use indicatif::ProgressBar;
-use rayon::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::{iter::Either, prelude::*};
 use serde_json::{Map, Value};
 
 use pix::{
-    cli::Commands,
+    check,
+    cli::{Commands, ConfigCommand, ImportCommand, NftState, RemoteCommand, UploadMetadata},
     config::{
-        create_global_config_paths, AppConfig, GlobalConfig, NftMakerGlobalConfig,
-        NftMakerLocalConfig, NftMakerNetwork, NftProjectId,
+        create_global_config_paths, AppConfig, ConfigFormat, GlobalConfig, MetadataFormat,
+        NftMakerGlobalConfig, NftMakerLocalConfig, NftMakerProjectConfig, NftProjectId, APIKEY_ENV,
+        CONFIG_FILES,
     },
+    hashlips,
+    journal::{Journal, JournalEntry, JOURNAL},
     layers::Layers,
+    manifest::{Manifest, TokenEntry},
     metadata,
-    nft_maker::{CreateProjectRequest, MetadataPlaceholder, NftMakerClient},
+    nft_maker::{
+        CreateProjectRequest, MetadataPlaceholder, NftMakerClient, NftUpload, NftUploadSubfile,
+        UploadNftResponse,
+    },
+    output::{self as output_files, TokenFiles},
+    policy,
     rarity::Rarity,
-    utils,
+    remote::{self, Status},
+    schema, utils,
 };
 
 const OUTPUT: &str = "output";
@@ -28,25 +46,93 @@ fn main() -> anyhow::Result<()> {
     let output = Path::new(OUTPUT);
 
     match cmds {
-        Commands::Auth => {
+        Commands::Auth { profile } => {
             let (_, global_config_path) = create_global_config_paths()?;
 
+            let mut global_config = GlobalConfig::new()?;
+
             let apikey = Password::new()
                 .with_prompt("NFT Maker API Key")
                 .interact()?;
 
-            let global_config = GlobalConfig {
-                nft_maker: Some(NftMakerGlobalConfig { apikey }),
-            };
+            match profile {
+                Some(profile) => {
+                    global_config
+                        .profiles
+                        .insert(profile, NftMakerGlobalConfig { apikey });
+                }
+                None => global_config.nft_maker = Some(NftMakerGlobalConfig { apikey }),
+            }
 
             let contents = serde_json::to_string_pretty(&global_config)?;
 
             fs::write(&global_config_path, contents)?;
         }
+        Commands::Check(args) => {
+            let config = AppConfig::new(args.path())?;
+
+            let problems = check::check(&config, Path::new(args.path()));
+
+            if !problems.is_empty() {
+                for problem in &problems {
+                    eprintln!("{}", problem);
+                }
+
+                return Err(anyhow!("{} problems found", problems.len()));
+            }
+
+            println!("no problems found");
+        }
         Commands::Clean => utils::clean(output)?,
+        Commands::Config {
+            command:
+                ConfigCommand::Convert {
+                    config_args,
+                    to,
+                    output,
+                },
+        } => {
+            let config_path = Path::new(config_args.path());
+
+            let (_, _, raw) = ConfigFormat::read(config_path)?;
+
+            schema::validate(&Value::Object(raw.clone()))
+                .with_context(|| format!("invalid config {}", config_path.display()))?;
+
+            let output = output.unwrap_or_else(|| config_path.with_extension(to.extension()));
+
+            if output.exists() {
+                return Err(anyhow!("{} already exists", output.display()));
+            }
+
+            fs::write(&output, to.to_string(&raw)?)?;
+
+            println!(
+                "wrote {}, remove {} to start using it",
+                output.display(),
+                config_path.display()
+            );
+        }
 
         Commands::Gen(args) => {
-            let config = AppConfig::new(&args.config)?;
+            let config = AppConfig::new(args.config_args.path())?;
+
+            let config_hash = utils::sha256_file(Path::new(args.config_args.path()))?;
+
+            config.validate_policy(Path::new(args.config_args.path()))?;
+
+            config.validate_metaplex()?;
+
+            config.validate_sets()?;
+
+            config.validate_attributes()?;
+
+            metadata::validate_templates(&config)?;
+
+            let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+
+            let mut rng = StdRng::seed_from_u64(seed);
+
             let progress = ProgressBar::new(config.amount as u64);
 
             let (layer_sets, unique_sets) = if let Some(sets) = &config.sets {
@@ -67,20 +153,22 @@ fn main() -> anyhow::Result<()> {
 
                     layers.load(
                         config.mode,
-                        &config.layers,
+                        &set.layers(&config.layers),
                         config.path.join(set.name.clone()),
                     )?;
 
                     let mut fail_count = 0;
 
-                    let mut uniques = HashSet::new();
+                    let mut seen = HashSet::new();
+
+                    let mut uniques = Vec::new();
 
                     let mut count = 1;
 
                     while count <= set.amount {
-                        let unique = layers.create_unique(&config.layers, &set.name);
+                        let unique = layers.create_unique(&set.name, &mut rng);
 
-                        if uniques.contains(&unique) {
+                        if seen.contains(&unique) {
                             fail_count += 1;
 
                             if fail_count > config.tolerance {
@@ -95,7 +183,9 @@ fn main() -> anyhow::Result<()> {
                             continue;
                         }
 
-                        uniques.insert(unique);
+                        seen.insert(unique.clone());
+
+                        uniques.push(unique);
 
                         count += 1;
                     }
@@ -111,18 +201,20 @@ fn main() -> anyhow::Result<()> {
             } else {
                 let mut layers = Layers::default();
 
-                layers.load(config.mode, &config.layers, config.path)?;
+                layers.load(config.mode, &config.layers, config.path.clone())?;
 
                 let mut fail_count = 0;
 
-                let mut uniques = HashSet::new();
+                let mut seen = HashSet::new();
+
+                let mut uniques = Vec::new();
 
                 let mut count = 1;
 
                 while count <= config.amount {
-                    let unique = layers.create_unique(&config.layers, "");
+                    let unique = layers.create_unique("", &mut rng);
 
-                    if uniques.contains(&unique) {
+                    if seen.contains(&unique) {
                         fail_count += 1;
 
                         if fail_count > config.tolerance {
@@ -137,7 +229,9 @@ fn main() -> anyhow::Result<()> {
                         continue;
                     }
 
-                    uniques.insert(unique);
+                    seen.insert(unique.clone());
+
+                    uniques.push(unique);
 
                     count += 1;
                 }
@@ -147,13 +241,27 @@ fn main() -> anyhow::Result<()> {
 
             utils::clean(output)?;
 
-            fs::create_dir(output)?;
+            output_files::prepare(&config, output)?;
+
+            // The set attribute of a set's tokens, when the config asks for one
+            let set_attribute = |set_index: usize| {
+                let key = config.set_attribute.as_deref()?;
+
+                let set = &config.sets.as_ref()?[set_index];
+
+                Some((key, set.name.as_str()))
+            };
 
             // Calculate rarity
             let mut rarity = Rarity::new(config.amount);
 
             for (uniques, set_index, _) in &unique_sets {
                 for unique in uniques {
+                    // sets are always counted, but only rank tokens as an attribute
+                    if let (Some(key), Some(sets)) = (config.set_key(), &config.sets) {
+                        rarity.count_trait(key, &sets[*set_index].name);
+                    }
+
                     for (index, trait_list) in unique.iter().zip(&layer_sets[*set_index].data) {
                         let nft_trait = &trait_list[*index];
 
@@ -162,19 +270,54 @@ fn main() -> anyhow::Result<()> {
                 }
             }
 
+            // Rank every token by rarity score, 1 being the rarest
+            let mut scores = Vec::new();
+
+            for (position, (uniques, set_index, _)) in unique_sets.iter().enumerate() {
+                for (index, unique) in uniques.iter().enumerate() {
+                    let traits = unique.iter().zip(&layer_sets[*set_index].data).map(
+                        |(index, trait_list)| {
+                            let nft_trait = &trait_list[*index];
+
+                            (nft_trait.layer.as_str(), nft_trait.name.as_str())
+                        },
+                    );
+
+                    let score = rarity.score(set_attribute(*set_index).into_iter().chain(traits));
+
+                    scores.push((position, index, score));
+                }
+            }
+
+            scores.sort_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+
+            let mut ranks: Vec<Vec<usize>> = unique_sets
+                .iter()
+                .map(|(uniques, _, _)| vec![0; uniques.len()])
+                .collect();
+
+            for (rank, (position, index, _)) in scores.into_iter().enumerate() {
+                ranks[position][index] = rank + 1;
+            }
+
             // Generate the images
-            unique_sets
+            let mut tokens: Vec<TokenEntry> = unique_sets
                 .par_iter()
-                .for_each(|(uniques, set_index, offset)| {
+                .enumerate()
+                .flat_map(|(position, (uniques, set_index, offset))| {
                     let layers = &layer_sets[*set_index];
 
+                    let set = config.sets.as_ref().map(|sets| &sets[*set_index]);
+
                     uniques
                         .iter()
                         .enumerate()
                         .collect::<Vec<(usize, &Vec<usize>)>>()
                         .par_iter()
-                        .for_each(|(mut count, unique)| {
-                            if config.start_at_one {
+                        .map(|(mut count, unique)| {
+                            let rank = ranks[position][count];
+
+                            if config.starts_at_one() {
                                 count += 1
                             }
 
@@ -182,15 +325,27 @@ fn main() -> anyhow::Result<()> {
 
                             let mut trait_info = Map::new();
 
-                            let folder_name =
-                                output.join(format!("{}#{}", config.name, count + offset));
-                            fs::create_dir(&folder_name)
-                                .expect("failed to created a folder for an NFT");
+                            if let Some((key, set)) = set_attribute(*set_index) {
+                                trait_info.insert(key.to_owned(), Value::String(set.to_owned()));
+                            }
+
+                            let number = count + offset;
+
+                            let files = TokenFiles::new(&config, output, number);
+
+                            if let Some(folder) = &files.folder {
+                                fs::create_dir(folder)
+                                    .expect("failed to created a folder for an NFT");
+                            }
 
-                            for (index, trait_list) in unique.iter().zip(&layers.data) {
+                            for ((index, trait_list), group) in
+                                unique.iter().zip(&layers.data).zip(&layers.groups)
+                            {
                                 let nft_trait = &trait_list[*index];
 
-                                trait_info.insert(
+                                metadata::insert_attribute(
+                                    &mut trait_info,
+                                    group.as_deref(),
                                     nft_trait.layer.to_owned(),
                                     Value::String(nft_trait.name.to_owned()),
                                 );
@@ -200,37 +355,87 @@ fn main() -> anyhow::Result<()> {
                                 }
                             }
 
-                            let nft_image_path =
-                                folder_name.join(format!("{}#{}.png", config.name, count + offset));
-                            let attributes_path = folder_name.join(format!(
-                                "{}#{}.json",
-                                config.name,
-                                count + offset
-                            ));
-                            let metadata_path = folder_name.join("metadata.json");
-
-                            base.save(nft_image_path).expect("failed to create image");
-
-                            let attributes = serde_json::to_string_pretty(&trait_info)
-                                .expect("failed to create attributes");
-
-                            fs::write(attributes_path, attributes)
-                                .expect("failed to create attributes");
-
-                            let meta = metadata::build_with_attributes(
-                                trait_info,
-                                config.policy_id.clone(),
-                                config.name.clone(),
-                                config.display_name.as_ref(),
-                                config.extra.clone(),
-                                count + offset,
-                            );
+                            let mut rng = StdRng::seed_from_u64(seed.wrapping_add(number as u64));
+
+                            for attribute in config.attributes.iter().flatten() {
+                                metadata::insert_attribute(
+                                    &mut trait_info,
+                                    attribute.group.as_deref(),
+                                    attribute.name.to_owned(),
+                                    Value::from(rng.gen_range(attribute.min..=attribute.max)),
+                                );
+                            }
 
-                            fs::write(metadata_path, meta).expect("failed to create metadata");
+                            base.save(&files.image).expect("failed to create image");
+
+                            if let Some(attributes_path) = &files.attributes {
+                                let attributes = serde_json::to_string_pretty(&trait_info)
+                                    .expect("failed to create attributes");
+
+                                fs::write(attributes_path, attributes)
+                                    .expect("failed to create attributes");
+                            }
+
+                            let meta = match config.metadata_format() {
+                                MetadataFormat::Cip25 => metadata::build_with_attributes(
+                                    trait_info, &config, set, number, rank,
+                                ),
+                                MetadataFormat::Erc721 => metadata::build_erc721(
+                                    &trait_info,
+                                    &config,
+                                    set,
+                                    TokenFiles::image_uri(&config, number),
+                                    number,
+                                    rank,
+                                ),
+                                MetadataFormat::Metaplex => metadata::build_metaplex(
+                                    &trait_info,
+                                    &config,
+                                    set,
+                                    number,
+                                    rank,
+                                ),
+                            }
+                            .expect("failed to render metadata");
+
+                            fs::write(&files.metadata, &meta).expect("failed to create metadata");
 
                             progress.inc(1);
-                        });
-                });
+
+                            let relative = |path: &Path| {
+                                path.strip_prefix(output)
+                                    .unwrap_or(path)
+                                    .display()
+                                    .to_string()
+                            };
+
+                            TokenEntry {
+                                number,
+                                asset_name: config.asset_name(number),
+                                set: set.map(|set| set.name.clone()),
+                                image: relative(&files.image),
+                                image_hash: utils::sha256_file(&files.image)
+                                    .expect("failed to hash image"),
+                                metadata: relative(&files.metadata),
+                                metadata_hash: utils::sha256(meta),
+                            }
+                        })
+                        .collect::<Vec<TokenEntry>>()
+                })
+                .collect();
+
+            tokens.sort_by_key(|token| token.number);
+
+            let manifest = Manifest {
+                name: config.name.clone(),
+                policy_id: config.policy_id.clone(),
+                config_hash,
+                seed,
+                provenance: Manifest::provenance(&tokens),
+                tokens,
+            };
+
+            manifest.save(output)?;
 
             let rarity_path = output.join("rarity.json");
 
@@ -239,30 +444,87 @@ fn main() -> anyhow::Result<()> {
             fs::write(rarity_path, rarity_data)?;
 
             progress.finish();
+
+            println!("seed: {}", manifest.seed);
+            println!("provenance: {}", manifest.provenance);
         }
 
         Commands::Metadata(args) => {
-            let config = AppConfig::new(&args.config)?;
+            let config = AppConfig::new(args.path())?;
 
             let template = metadata::build_template(&config);
 
             println!("{}", template);
         }
 
-        Commands::New { name } => {
+        Commands::Import {
+            command:
+                ImportCommand::Hashlips {
+                    dir,
+                    output,
+                    format,
+                },
+        } => {
+            if let Some(existing) = CONFIG_FILES
+                .iter()
+                .map(|file| output.join(file))
+                .find(|path| path.exists())
+            {
+                return Err(anyhow!("{} already exists", existing.display()));
+            }
+
+            fs::create_dir_all(&output)?;
+
+            let (config, warnings) = hashlips::import(&dir, &output)?;
+
+            for warning in warnings {
+                eprintln!("warning: {}", warning);
+            }
+
+            let config_file_path = output.join(format!("pix.{}", format.extension()));
+
+            fs::write(&config_file_path, format.to_string(&config)?)?;
+
+            println!(
+                "imported {} tokens in {} layers into {}",
+                config.amount,
+                config.layers.len(),
+                config_file_path.display()
+            );
+        }
+        Commands::New(args) => {
             let global_config = GlobalConfig::new()?;
 
-            let root_dir = Path::new(&name);
+            let name = &args.name;
+
+            let root_dir = Path::new(name);
 
             if root_dir.exists() {
                 return Err(anyhow!("{} already exists", root_dir.display()));
             }
 
+            let script = args.policy.script()?;
+
+            let mut app_config =
+                AppConfig::prompt(script.as_ref().map(|script| script.policy_id()))?;
+
+            // check the policy before anything is written, so a mistake can just be retried
+            let policy = args.policy.load(app_config.policy_id.as_deref())?;
+
+            let project = NftMakerProjectConfig::prompt(&args, script.as_ref())?;
+
             fs::create_dir(root_dir)?;
 
-            let mut app_config = AppConfig::prompt()?;
+            if let Some(script) = &args.policy.policy_script {
+                let script = fs::canonicalize(script)
+                    .with_context(|| format!("could not find {}", script.display()))?;
+
+                // relative to the project, so the project folder can move
+                app_config.policy_script =
+                    Some(utils::relative_path(&script, &fs::canonicalize(root_dir)?));
+            }
 
-            let config_file_path = root_dir.join("pix.json");
+            let config_file_path = root_dir.join(format!("pix.{}", args.format.extension()));
 
             let images_path = root_dir.join(&app_config.path);
 
@@ -274,7 +536,9 @@ fn main() -> anyhow::Result<()> {
                 fs::create_dir(&layer_path)?;
             }
 
-            if let Some(nft_maker_config) = global_config.nft_maker {
+            app_config.profile = args.profile.clone();
+
+            if let Some(apikey) = global_config.apikey(args.profile.as_deref())? {
                 if Confirm::with_theme(&ColorfulTheme::default())
                     .with_prompt("would you like to create a new project on https://nft-maker.io?")
                     .default(false)
@@ -282,26 +546,29 @@ fn main() -> anyhow::Result<()> {
                 {
                     let metadata = metadata::build_template(&app_config);
 
-                    let body = CreateProjectRequest::new(
-                        &app_config,
-                        metadata,
-                        "2022-02-20T00:00:00.988Z".to_string(),
-                    );
+                    let mut body = CreateProjectRequest::new(&app_config, metadata, &project);
 
-                    let nft_maker =
-                        NftMakerClient::new(nft_maker_config.apikey, NftMakerNetwork::Mainnet)?;
+                    if let Some(script) = &script {
+                        let locks_at = policy::lock_date(script, project.network, None)?;
+
+                        body = body.with_policy(policy, locks_at);
+                    }
+
+                    let nft_maker = NftMakerClient::new(apikey, project.network)?;
 
                     let data = nft_maker.create_project(&body)?;
 
                     app_config.nft_maker = Some(NftMakerLocalConfig {
-                        network: NftMakerNetwork::Mainnet,
-                        apikey: "".to_string(),
+                        network: project.network,
                         nft_project_id: NftProjectId::Id(data.project_id),
+                        ..Default::default()
                     })
                 }
             }
 
-            let contents = serde_json::to_string_pretty(&app_config)?;
+            app_config.nft_maker_project = Some(project);
+
+            let contents = args.format.to_string(&app_config)?;
 
             fs::write(config_file_path, contents)?;
 
@@ -309,31 +576,359 @@ fn main() -> anyhow::Result<()> {
             println!("cd {}", &name);
             println!("and add some traits into the images/ directory 🚀");
         }
+        Commands::Policy(args) => {
+            let config = AppConfig::new(args.path())?;
+
+            let script = config
+                .validate_policy(Path::new(args.path()))?
+                .context("set policy_script to the policy's native script")?;
+
+            println!("policy id: {}", script.policy_id());
+
+            match script.lock_slot() {
+                Some(slot) => println!("locks after slot {}", slot),
+                None => println!("never locks"),
+            }
+        }
+        Commands::Remote {
+            command:
+                RemoteCommand::Create {
+                    config_args,
+                    policy,
+                },
+        } => {
+            let config_path = Path::new(config_args.path());
+
+            // edit the file itself so nothing loaded from elsewhere ends up in it
+            let (format, contents, raw) = ConfigFormat::read(config_path)?;
+
+            if raw.contains_key("nft_maker") {
+                return Err(anyhow!(
+                    "{} already has an nft_maker project",
+                    config_path.display()
+                ));
+            }
+
+            let app_config: AppConfig = serde_json::from_value(Value::Object(raw.clone()))
+                .with_context(|| format!("failed to parse {}", config_path.display()))?;
+
+            // the project's own policy has to be the one nft-maker.io mints with
+            let configured = app_config.validate_policy(config_path)?;
+
+            let script = policy.script()?;
+
+            match (&configured, &script) {
+                (Some(_), None) => {
+                    return Err(anyhow!(
+                        "the project's policy_script needs --policy-script, --policy-skey and --policy-vkey, or nft-maker.io would make a new policy"
+                    ))
+                }
+                (Some(configured), Some(script)) if configured.policy_id() != script.policy_id() => {
+                    return Err(anyhow!(
+                        "--policy-script has policy id {}, but the project's policy_script has {}",
+                        script.policy_id(),
+                        configured.policy_id()
+                    ))
+                }
+                _ => (),
+            }
+
+            let apikey = GlobalConfig::new()?
+                .apikey(app_config.profile.as_deref())?
+                .with_context(|| {
+                    format!(
+                        "please run pix auth or set {} to create projects on nft-maker.io",
+                        APIKEY_ENV
+                    )
+                })?;
+
+            let project = app_config.nft_maker_project.clone().unwrap_or_default();
+
+            let mut body = CreateProjectRequest::new(
+                &app_config,
+                metadata::build_template(&app_config),
+                &project,
+            );
+
+            if let Some(script) = script {
+                // only a lock date from the config can disagree with the script
+                let policy_locks_at = app_config
+                    .nft_maker_project
+                    .as_ref()
+                    .map(|project| project.policy_locks_at.as_str());
+
+                let locks_at = policy::lock_date(&script, project.network, policy_locks_at)?;
+
+                body = body.with_policy(policy.load(app_config.policy_id.as_deref())?, locks_at);
+            }
+
+            let nft_maker = NftMakerClient::new(apikey, project.network)?;
+
+            let data = nft_maker.create_project(&body)?;
+
+            let local_config = NftMakerLocalConfig {
+                network: project.network,
+                nft_project_id: NftProjectId::Id(data.project_id),
+                ..Default::default()
+            };
+
+            let contents =
+                format.insert(&contents, "nft_maker", serde_json::to_value(&local_config)?)?;
+
+            fs::write(config_path, contents)?;
+
+            println!(
+                "created project {} with policy {}",
+                data.project_id,
+                data.policy_id.as_deref().unwrap_or("-")
+            );
+        }
+        Commands::Remote { command } => {
+            let config_path = command.config();
+
+            let config = AppConfig::new(config_path)?;
+
+            let nft_maker_config = config
+                .nft_maker
+                .as_ref()
+                .context("please provide an nft_maker config to inspect the project")?;
+
+            let nft_maker = NftMakerClient::from_config(nft_maker_config)?;
+
+            let project_id = &nft_maker_config.nft_project_id;
+
+            let mut journal = Journal::load(config_path, project_id.to_string())?;
+
+            let local: Vec<remote::Local> = if output.exists() {
+                // the manifest knows what each image looked like when it was generated
+                let image_hashes: HashMap<usize, String> = Manifest::load(output)
+                    .map(|manifest| {
+                        manifest
+                            .tokens
+                            .into_iter()
+                            .map(|token| (token.number, token.image_hash))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                output_files::local_tokens(&config, output)?
+                    .into_iter()
+                    .map(|(number, _)| remote::Local {
+                        number,
+                        image_hash: image_hashes.get(&number).cloned(),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            match command {
+                RemoteCommand::Create { .. } => unreachable!("remote create is handled above"),
+                RemoteCommand::List(args) => {
+                    let nfts = nft_maker.list_nfts(project_id, &args.state.to_string())?;
+
+                    // only a full listing can tell what is missing
+                    let filtered = args.state != NftState::All;
+
+                    let rows = remote::diff(
+                        &config,
+                        if filtered { &[] } else { &local },
+                        &journal,
+                        &nfts,
+                    );
+
+                    println!(
+                        "{:<8} {:<10} {:<48} {:<46} STATUS",
+                        "TOKEN", "STATE", "ASSET ID", "IPFS HASH"
+                    );
+
+                    for row in &rows {
+                        let nft = row.nft.as_ref();
+
+                        println!(
+                            "{:<8} {:<10} {:<48} {:<46} {}",
+                            row.number
+                                .map_or_else(|| String::from("-"), |n| n.to_string()),
+                            nft.map_or_else(|| String::from("-"), |nft| nft.status()),
+                            nft.and_then(|nft| nft.assetid.as_deref()).unwrap_or("-"),
+                            nft.and_then(|nft| nft.ipfshash.as_deref()).unwrap_or("-"),
+                            if filtered {
+                                String::new()
+                            } else {
+                                row.status.to_string()
+                            }
+                        );
+                    }
+
+                    if !filtered {
+                        let count = |status| rows.iter().filter(|row| row.status == status).count();
+
+                        println!(
+                            "\n{} remote, {} local: {} ok, {} missing, {} extra, {} mismatched",
+                            nfts.len(),
+                            local.len(),
+                            count(Status::Ok),
+                            count(Status::Missing),
+                            count(Status::Extra),
+                            count(Status::Mismatch)
+                        );
+                    }
+                }
+                RemoteCommand::Delete { tokens, .. } => {
+                    let deleted =
+                        remote::delete(&nft_maker, project_id, &config, &mut journal, &tokens.0)?;
+
+                    println!("{} tokens deleted from nft-maker.io", deleted.len());
+                }
+                RemoteCommand::Show { token, .. } => {
+                    let nft = match journal.get(token) {
+                        Some(JournalEntry::Uploaded {
+                            nft_id, nft_uid, ..
+                        }) => nft_maker.get_nft(project_id, *nft_id, nft_uid.as_deref())?,
+                        _ => {
+                            let nfts = nft_maker.list_nfts(project_id, "all")?;
+
+                            remote::find(&config, &journal, &nfts, token)
+                                .cloned()
+                                .with_context(|| {
+                                    format!("token {} is not on nft-maker.io", token)
+                                })?
+                        }
+                    };
+
+                    println!("{}", serde_json::to_string_pretty(&nft)?);
+
+                    let rows = remote::diff(
+                        &config,
+                        &local
+                            .into_iter()
+                            .filter(|local| local.number == token)
+                            .collect::<Vec<_>>(),
+                        &journal,
+                        std::slice::from_ref(&nft),
+                    );
+
+                    if let Some(row) = rows.first() {
+                        println!("\nstate: {}, status: {}", nft.status(), row.status);
+                    }
+                }
+            }
+        }
+        Commands::Schema => {
+            println!("{}", serde_json::to_string_pretty(&schema::schema())?);
+        }
         Commands::Upload(args) => {
             if !output.exists() {
                 return Err(anyhow!("no output found, try running gen first"));
             }
 
-            let config = AppConfig::new(&args.config)?;
+            let config = AppConfig::new(args.config_args.path())?;
 
-            if let Some(nft_maker_config) = config.nft_maker {
-                let nft_maker =
-                    NftMakerClient::new(nft_maker_config.apikey, nft_maker_config.network)?;
+            if args.metadata == UploadMetadata::Full {
+                if config.metadata_format() != MetadataFormat::Cip25 {
+                    return Err(anyhow!("uploading full metadata needs cip25 metadata"));
+                }
 
-                let output_dir = output
-                    .read_dir()
-                    .with_context(|| format!("{} is not a folder", output.display()))?
-                    .map(|dir| dir.unwrap().path())
-                    .filter(|path| path.is_dir());
+                // metadata.json would name the policy `<policy_id>`
+                if config.policy_id.is_none() {
+                    return Err(anyhow!(
+                        "uploading full metadata needs a policy_id in the config"
+                    ));
+                }
+            }
 
-                let progress = ProgressBar::new(config.amount as u64);
+            // placeholders fill in the project's template, which only knows the top level config
+            if args.metadata == UploadMetadata::Placeholders
+                && config
+                    .sets
+                    .iter()
+                    .flatten()
+                    .any(|set| set.layers.is_some() || set.extra.is_some())
+            {
+                return Err(anyhow!(
+                    "sets with their own layers or extra need --metadata full, placeholders follow the top level config"
+                ));
+            }
 
-                for nft_path in output_dir {
-                    let nft_name = nft_path.file_name().unwrap().to_str().unwrap();
+            // older outputs have no manifest, their tokens are named after the project
+            let token_sets: HashMap<usize, String> = Manifest::load(output)
+                .map(|manifest| {
+                    manifest
+                        .tokens
+                        .into_iter()
+                        .filter_map(|token| Some((token.number, token.set?)))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            if let Some(nft_maker_config) = &config.nft_maker {
+                let nft_maker = NftMakerClient::from_config(nft_maker_config)?;
+
+                let mut journal = Journal::load(
+                    args.config_args.path(),
+                    nft_maker_config.nft_project_id.to_string(),
+                )?;
+
+                let mut nft_paths = output_files::local_tokens(&config, output)?;
+
+                if let Some(replace) = &args.replace {
+                    // never delete a token there is no new render for
+                    let missing: Vec<String> = replace
+                        .0
+                        .iter()
+                        .filter(|number| !nft_paths.iter().any(|(local, _)| local == *number))
+                        .map(|number| number.to_string())
+                        .collect();
+
+                    if !missing.is_empty() {
+                        return Err(anyhow!(
+                            "no output found for tokens {}, try running gen first",
+                            missing.join(", ")
+                        ));
+                    }
 
-                    let split_name: Vec<&str> = nft_name.split('#').collect();
+                    let deleted = remote::delete(
+                        &nft_maker,
+                        &nft_maker_config.nft_project_id,
+                        &config,
+                        &mut journal,
+                        &replace.0,
+                    )?;
 
-                    let number = split_name[1];
+                    println!("{} tokens deleted from nft-maker.io", deleted.len());
+
+                    nft_paths.retain(|(number, _)| replace.0.contains(number));
+                }
+
+                // skip what already made it, and anything new when only retrying failures
+                nft_paths.retain(|(number, _)| match journal.get(*number) {
+                    Some(entry) => !entry.is_uploaded(),
+                    None => !args.retry_failed,
+                });
+
+                let config_path = Path::new(args.config_args.path());
+
+                for subfile in nft_maker_config.subfiles.iter().flatten() {
+                    for (_, nft_path) in &nft_paths {
+                        let subfile_path = subfile.path(config_path, nft_path);
+
+                        if !subfile_path.is_file() {
+                            eprintln!(
+                                "warning: {} is missing, the token is uploaded without it",
+                                subfile_path.display()
+                            );
+                        }
+                    }
+                }
+
+                let progress = ProgressBar::new(nft_paths.len() as u64);
+
+                let journal = Mutex::new(journal);
+
+                let upload = |number: usize,
+                              nft_path: &Path|
+                 -> anyhow::Result<(UploadNftResponse, String)> {
+                    let nft_name = nft_path.file_name().unwrap().to_str().unwrap();
 
                     let nft_file_path = nft_path.join(format!("{}.png", nft_name));
 
@@ -345,52 +940,217 @@ fn main() -> anyhow::Result<()> {
 
                     let nft_attributes = serde_json::from_reader(&nft_attributes_file)?;
 
-                    if let Value::Object(attributes) = nft_attributes {
-                        let nft_base64 = base64::encode(nft);
-
-                        let metadata_placeholder: Vec<MetadataPlaceholder> = attributes
-                            .values()
-                            .enumerate()
-                            .map(|(index, attr)| {
-                                if let Value::String(attr) = attr {
-                                    MetadataPlaceholder {
-                                        name: Some(format!("attribute{}", index)),
-                                        value: Some(attr.to_owned()),
-                                    }
-                                } else {
-                                    eprintln!("attribute values should be strings");
-
-                                    process::exit(1);
-                                }
+                    let attributes = match nft_attributes {
+                        Value::Object(attributes) => attributes,
+                        _ => return Err(anyhow!("failed to read nft attributes")),
+                    };
+
+                    let image_hash = utils::sha256(&nft);
+
+                    let nft_base64 = base64::encode(nft);
+
+                    let asset_name = format!("{}{}", config.name, number);
+
+                    let mut upload = NftUpload {
+                        asset_name: asset_name.clone(),
+                        mimetype: String::from("image/png"),
+                        displayname: format!(
+                            "{} #{}",
+                            config.display_name(
+                                token_sets.get(&number).and_then(|set| config.set(set))
+                            ),
+                            number
+                        ),
+                        file_from_base64: nft_base64,
+                        ..Default::default()
+                    };
+
+                    match args.metadata {
+                        UploadMetadata::Placeholders => {
+                            upload.metadata_placeholder = metadata::placeholder_values(&attributes)
+                                .into_iter()
+                                .enumerate()
+                                .map(|(index, value)| MetadataPlaceholder {
+                                    name: Some(format!("attribute{}", index)),
+                                    value: Some(value),
+                                })
+                                .collect();
+
+                            if let Some(set) = token_sets.get(&number) {
+                                upload.metadata_placeholder.push(MetadataPlaceholder {
+                                    name: Some(String::from("set")),
+                                    value: Some(set.clone()),
+                                });
+                            }
+                        }
+                        UploadMetadata::Full => {
+                            let metadata_path = nft_path.join("metadata.json");
+
+                            let contents =
+                                fs::read_to_string(&metadata_path).with_context(|| {
+                                    format!("could not read {}", metadata_path.display())
+                                })?;
+
+                            let json: Value = serde_json::from_str(&contents)?;
+
+                            let asset = metadata::cip25_asset(&json, &config.asset_name(number))?;
+
+                            if let Some(name) = asset.get("name").and_then(Value::as_str) {
+                                upload.displayname = name.to_owned();
+                            }
+
+                            upload.metadata = Some(contents);
+                        }
+                    }
+
+                    for subfile in nft_maker_config.subfiles.iter().flatten() {
+                        let subfile_path = subfile.path(config_path, nft_path);
+
+                        // already warned about
+                        if !subfile_path.is_file() {
+                            continue;
+                        }
+
+                        let mimetype = subfile
+                            .mimetype
+                            .as_deref()
+                            .or_else(|| utils::mime_type(&subfile_path))
+                            .with_context(|| {
+                                format!(
+                                    "unknown mime type for {}, set one in the subfile config",
+                                    subfile_path.display()
+                                )
+                            })?;
+
+                        let metadata_placeholder = subfile
+                            .metadata_placeholder
+                            .iter()
+                            .flatten()
+                            .map(|(name, value)| MetadataPlaceholder {
+                                name: Some(name.to_owned()),
+                                value: Some(match value {
+                                    Value::String(value) => value.to_owned(),
+                                    value => value.to_string(),
+                                }),
                             })
                             .collect();
 
-                        nft_maker.upload_nft(
-                            &nft_maker_config.nft_project_id,
-                            format!("{}{}", config.name, number),
-                            String::from("image/png"),
-                            format!(
-                                "{} #{}",
-                                config.display_name.as_ref().unwrap_or(&config.name),
-                                number
-                            ),
-                            nft_base64,
+                        upload.subfiles.push(NftUploadSubfile {
+                            mimetype: mimetype.to_owned(),
+                            file_from_base64: base64::encode(fs::read(&subfile_path)?),
+                            description: subfile.description.clone(),
                             metadata_placeholder,
-                        )?;
+                        });
+                    }
 
-                        progress.inc(1);
+                    let response =
+                        nft_maker.upload_nft(&nft_maker_config.nft_project_id, upload)?;
 
-                        std::thread::sleep(Duration::from_micros(100));
-                    } else {
-                        return Err(anyhow!("failed to read nft attributes"));
-                    }
-                }
+                    Ok((response, image_hash))
+                };
+
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(args.concurrency.max(1))
+                    .build()?;
+
+                // the numbers whose upload failed, and those that were uploaded but not recorded
+                let (mut failures, mut unrecorded): (Vec<_>, Vec<_>) = pool.install(|| {
+                    nft_paths
+                        .par_iter()
+                        .filter_map(|(number, nft_path)| {
+                            let uploaded = upload(*number, nft_path);
+
+                            progress.inc(1);
+
+                            let mut journal = journal.lock().expect("journal lock poisoned");
+
+                            match uploaded {
+                                Ok((response, image_hash)) => journal
+                                    .record(*number, JournalEntry::uploaded(&response, image_hash))
+                                    .err()
+                                    .map(|e| Either::Right((*number, e))),
+                                Err(e) => {
+                                    let entry = JournalEntry::Failed {
+                                        error: e.to_string(),
+                                    };
+
+                                    let e = match journal.record(*number, entry) {
+                                        Ok(()) => e,
+                                        Err(journal_error) => {
+                                            e.context(format!("{:#}", journal_error))
+                                        }
+                                    };
+
+                                    Some(Either::Left((*number, e)))
+                                }
+                            }
+                        })
+                        .partition_map(|outcome| outcome)
+                });
 
                 progress.finish();
+
+                if !failures.is_empty() || !unrecorded.is_empty() {
+                    eprintln!();
+                }
+
+                failures.sort_by_key(|(number, _)| *number);
+
+                for (number, e) in &failures {
+                    eprintln!(
+                        "{}#{} failed to upload\nReason: {:#}",
+                        config.name, number, e
+                    );
+                }
+
+                unrecorded.sort_by_key(|(number, _)| *number);
+
+                for (number, e) in &unrecorded {
+                    eprintln!(
+                        "{}#{} was uploaded but could not be written to {}\nReason: {:#}",
+                        config.name, number, JOURNAL, e
+                    );
+                }
+
+                if !unrecorded.is_empty() {
+                    return Err(anyhow!(
+                        "{} uploaded tokens are missing from {}, check pix remote list before uploading again",
+                        unrecorded.len(),
+                        JOURNAL
+                    ));
+                }
+
+                if !failures.is_empty() {
+                    return Err(anyhow!(
+                        "{} uploads failed, rerun with --retry-failed to try them again",
+                        failures.len()
+                    ));
+                }
             } else {
                 return Err(anyhow!("please provide an nft_maker config to upload"));
             }
         }
+        Commands::Verify(args) => {
+            let manifest = Manifest::load(output)?;
+
+            let config_hash = utils::sha256_file(Path::new(args.path()))?;
+
+            let problems = manifest.verify(output, &config_hash);
+
+            if !problems.is_empty() {
+                for problem in &problems {
+                    eprintln!("{}", problem);
+                }
+
+                return Err(anyhow!("{} problems found", problems.len()));
+            }
+
+            println!(
+                "{} tokens verified, provenance {}",
+                manifest.tokens.len(),
+                manifest.provenance
+            );
+        }
     }
 
     Ok(())
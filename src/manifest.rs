@@ -0,0 +1,96 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+pub const MANIFEST: &str = "collection.json";
+
+/// Describes a generated collection so it can be published and verified later
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Manifest {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy_id: Option<String>,
+    pub config_hash: String,
+    pub seed: u64,
+    /// The hash of every image hash concatenated in token order
+    pub provenance: String,
+    pub tokens: Vec<TokenEntry>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TokenEntry {
+    pub number: usize,
+    pub asset_name: String,
+    /// The set the token was generated from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set: Option<String>,
+    pub image: String,
+    pub image_hash: String,
+    pub metadata: String,
+    pub metadata_hash: String,
+}
+
+impl Manifest {
+    pub fn provenance(tokens: &[TokenEntry]) -> String {
+        let hashes: String = tokens
+            .iter()
+            .map(|token| token.image_hash.as_str())
+            .collect();
+
+        utils::sha256(hashes)
+    }
+
+    pub fn load(output: &Path) -> anyhow::Result<Self> {
+        let path = output.join(MANIFEST);
+
+        let file = fs::File::open(&path)
+            .with_context(|| format!("could not open {}, try running gen first", path.display()))?;
+
+        serde_json::from_reader(file).with_context(|| format!("could not parse {}", path.display()))
+    }
+
+    pub fn save(&self, output: &Path) -> anyhow::Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+
+        fs::write(output.join(MANIFEST), contents)?;
+
+        Ok(())
+    }
+
+    /// Re-hash every file in the output and report anything that doesn't match
+    pub fn verify(&self, output: &Path, config_hash: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.config_hash != config_hash {
+            problems.push(String::from(
+                "config has changed since the collection was generated",
+            ));
+        }
+
+        if Self::provenance(&self.tokens) != self.provenance {
+            problems.push(String::from(
+                "provenance hash does not match the image hashes",
+            ));
+        }
+
+        for token in &self.tokens {
+            let files = [
+                (&token.image, &token.image_hash),
+                (&token.metadata, &token.metadata_hash),
+            ];
+
+            for (file, hash) in files {
+                match utils::sha256_file(&output.join(file)) {
+                    Ok(actual) if &actual == hash => (),
+                    Ok(_) => problems.push(format!("{} has been modified", file)),
+                    Err(_) => problems.push(format!("{} is missing", file)),
+                }
+            }
+        }
+
+        problems
+    }
+}
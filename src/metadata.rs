@@ -1,26 +1,397 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Context};
 use serde::Serialize;
 use serde_json::{json, Map, Value};
 
-use crate::config::AppConfig;
+use crate::{
+    config::{AppConfig, MetadataFormat, SetConfig},
+    template::{self, Template},
+};
 
 #[derive(Serialize)]
 pub struct Attribute {
+    #[serde(rename = "trait_type")]
     pub name: String,
-    pub value: String,
+    pub value: Value,
 }
 
-pub fn build_template(config: &AppConfig) -> String {
+/// Insert an attribute, nesting it under its dot separated group if it has one
+pub fn insert_attribute(
+    attributes: &mut Map<String, Value>,
+    group: Option<&str>,
+    name: String,
+    value: Value,
+) {
+    let mut target = attributes;
+
+    for key in group.into_iter().flat_map(|group| group.split('.')) {
+        let entry = target
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+
+        target = entry.as_object_mut().expect("just made this an object");
+    }
+
+    target.insert(name, value);
+}
+
+/// Every attribute value in order, keyed by its dot separated path
+pub fn flatten(attributes: &Map<String, Value>) -> Vec<(String, &Value)> {
+    let mut flat = Vec::new();
+
+    for (key, value) in attributes {
+        match value {
+            Value::Object(group) => {
+                flat.extend(
+                    flatten(group)
+                        .into_iter()
+                        .map(|(path, value)| (format!("{}.{}", key, path), value)),
+                );
+            }
+            value => flat.push((key.to_owned(), value)),
+        }
+    }
+
+    flat
+}
+
+/// The values for NFT Maker's `attributeN` placeholders, in the same order as the template
+pub fn placeholder_values(attributes: &Map<String, Value>) -> Vec<String> {
+    flatten(attributes)
+        .into_iter()
+        .map(|(_, value)| match value {
+            Value::String(value) => value.to_owned(),
+            value => value.to_string(),
+        })
+        .collect()
+}
+
+fn attribute_list(attributes: &Map<String, Value>) -> Vec<Attribute> {
+    flatten(attributes)
+        .into_iter()
+        .map(|(path, value)| Attribute {
+            name: path.rsplit('.').next().unwrap_or(&path).to_owned(),
+            value: value.clone(),
+        })
+        .collect()
+}
+
+/// The attribute layout with `<attributeN>` placeholders in place of values
+fn template_attributes(config: &AppConfig) -> Map<String, Value> {
     let mut attributes = Map::new();
 
-    for (index, attr) in config.layers.iter().enumerate() {
-        let template = Value::String(format!("<attribute{}>", index));
+    // the set goes first, as it does on generated tokens
+    let set = config
+        .set_attribute
+        .iter()
+        .filter(|_| config.sets.is_some())
+        .map(|key| (None, key));
 
-        attributes.insert(
-            attr.display_name.as_ref().unwrap_or(&attr.name).to_owned(),
-            template,
-        );
+    let layers = config.layers.iter().map(|layer| {
+        (
+            layer.group.as_deref(),
+            layer.display_name.as_ref().unwrap_or(&layer.name),
+        )
+    });
+
+    let generated = config
+        .attributes
+        .iter()
+        .flatten()
+        .map(|attribute| (attribute.group.as_deref(), &attribute.name));
+
+    for (group, name) in set.chain(layers).chain(generated) {
+        insert_attribute(&mut attributes, group, name.to_owned(), Value::Null);
     }
 
+    // number the placeholders in the order `flatten` lists them, which groups attributes together
+    number_placeholders(&mut attributes, &mut 0);
+
+    attributes
+}
+
+/// The `<set>` placeholder, for collections made of sets
+fn template_set(config: &AppConfig) -> Option<String> {
+    config.sets.as_ref().map(|_| String::from("<set>"))
+}
+
+fn number_placeholders(attributes: &mut Map<String, Value>, next: &mut usize) {
+    for value in attributes.values_mut() {
+        match value {
+            Value::Object(group) => number_placeholders(group, next),
+            value => {
+                *value = Value::String(format!("<attribute{}>", next));
+
+                *next += 1;
+            }
+        }
+    }
+}
+
+/// The templated values of a single token, shared by every metadata format
+pub struct Token {
+    pub name: String,
+    pub description: Option<String>,
+    /// The set the token belongs to, written next to its attributes
+    pub set: Option<String>,
+    pub extra: Option<Map<String, Value>>,
+}
+
+impl Token {
+    pub fn new(
+        config: &AppConfig,
+        set: Option<&SetConfig>,
+        attributes: &Map<String, Value>,
+        count: usize,
+        rank: usize,
+    ) -> anyhow::Result<Self> {
+        let metadata = config.metadata.as_ref();
+
+        let display_name = config.display_name(set);
+
+        let mut vars = Map::new();
+
+        for (path, value) in flatten(attributes) {
+            if let Some((_, name)) = path.rsplit_once('.') {
+                vars.insert(name.to_owned(), value.clone());
+            }
+
+            vars.insert(path, value.clone());
+        }
+
+        vars.insert(String::from("number"), json!(count));
+        vars.insert(String::from("rank"), json!(rank));
+        vars.insert(String::from("amount"), json!(config.amount));
+        vars.insert(String::from("name"), json!(config.name));
+        vars.insert(String::from("display_name"), json!(display_name));
+
+        if let Some(set) = set {
+            vars.insert(String::from("set"), json!(set.name));
+        }
+
+        let name = match metadata.and_then(|metadata| metadata.name.as_ref()) {
+            Some(name) => template::render(name, &vars)?,
+            None => format!("{} #{}", display_name, count),
+        };
+
+        let description = metadata
+            .and_then(|metadata| metadata.description.as_ref())
+            .map(|description| template::render(description, &vars))
+            .transpose()?;
+
+        let set_extra = set.and_then(|set| set.extra.as_ref());
+
+        // the set's fields win over the top level ones
+        let extra = match (&config.extra, set_extra) {
+            (None, None) => None,
+            (extra, set_extra) => Some(
+                extra
+                    .iter()
+                    .chain(set_extra)
+                    .flatten()
+                    .map(|(key, value)| Ok((key.to_owned(), render_value(value, &vars)?)))
+                    .collect::<anyhow::Result<Map<String, Value>>>()?,
+            ),
+        };
+
+        Ok(Self {
+            name,
+            description,
+            set: set.map(|set| set.name.clone()),
+            extra,
+        })
+    }
+}
+
+fn render_value(value: &Value, vars: &Map<String, Value>) -> anyhow::Result<Value> {
+    Ok(match value {
+        Value::String(value) => Value::String(template::render(value, vars)?),
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| render_value(value, vars))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Value::Object(values) => Value::Object(
+            values
+                .iter()
+                .map(|(key, value)| Ok((key.to_owned(), render_value(value, vars)?)))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        value => value.clone(),
+    })
+}
+
+/// Parse every template in the config so mistakes surface before generation starts
+pub fn validate_templates(config: &AppConfig) -> anyhow::Result<()> {
+    let known = known_variables(config);
+
+    let check = |source: &str| -> anyhow::Result<()> {
+        let template = Template::parse(source)?;
+
+        match template
+            .variables()
+            .into_iter()
+            .find(|name| !known.contains(*name))
+        {
+            Some(name) => Err(anyhow!(
+                "unknown variable `{}` in template `{}`",
+                name,
+                source
+            )),
+            None => Ok(()),
+        }
+    };
+
+    if let Some(metadata) = &config.metadata {
+        for template in metadata.name.iter().chain(&metadata.description) {
+            check(template)?;
+        }
+    }
+
+    fn validate_value(
+        check: &dyn Fn(&str) -> anyhow::Result<()>,
+        key: &str,
+        value: &Value,
+    ) -> anyhow::Result<()> {
+        match value {
+            Value::String(value) => {
+                check(value).with_context(|| format!("in extra field `{}`", key))?;
+            }
+            Value::Array(values) => {
+                for value in values {
+                    validate_value(check, key, value)?;
+                }
+            }
+            Value::Object(values) => {
+                for (key, value) in values {
+                    validate_value(check, key, value)?;
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    let set_extras = config
+        .sets
+        .iter()
+        .flatten()
+        .filter_map(|set| set.extra.as_ref());
+
+    for extra in config.extra.iter().chain(set_extras) {
+        for (key, value) in extra {
+            validate_value(&check, key, value)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Every variable a template can use: the built-ins and each attribute by name and by path
+fn known_variables(config: &AppConfig) -> HashSet<String> {
+    let mut known: HashSet<String> = ["number", "rank", "amount", "name", "display_name"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+
+    let mut attributes = template_attributes(config);
+
+    if let Some(sets) = &config.sets {
+        known.insert(String::from("set"));
+
+        for layer in sets.iter().flat_map(|set| set.layers(&config.layers)) {
+            insert_attribute(
+                &mut attributes,
+                layer.group.as_deref(),
+                layer.display_name.unwrap_or(layer.name),
+                Value::Null,
+            );
+        }
+    }
+
+    for (path, _) in flatten(&attributes) {
+        if let Some((_, name)) = path.rsplit_once('.') {
+            known.insert(name.to_owned());
+        }
+
+        known.insert(path);
+    }
+
+    known
+}
+
+/// Find the single asset in generated CIP-25 metadata, making sure it is named `asset_name`
+pub fn cip25_asset<'a>(
+    metadata: &'a Value,
+    asset_name: &str,
+) -> anyhow::Result<&'a Map<String, Value>> {
+    let policies = metadata
+        .get("721")
+        .and_then(Value::as_object)
+        .context("metadata is missing the 721 label")?;
+
+    let mut assets = policies
+        .iter()
+        .filter(|(key, _)| key.as_str() != "version")
+        .filter_map(|(_, assets)| assets.as_object())
+        .flatten();
+
+    let (name, asset) = match (assets.next(), assets.next()) {
+        (Some(asset), None) => asset,
+        _ => return Err(anyhow!("metadata must describe exactly one asset")),
+    };
+
+    if name != asset_name {
+        return Err(anyhow!(
+            "metadata is for asset {} but NFT Maker will name it {}",
+            name,
+            asset_name
+        ));
+    }
+
+    asset
+        .as_object()
+        .with_context(|| format!("metadata for {} is not an object", name))
+}
+
+/// CIP-25 limits strings to 64 bytes, longer ones have to be split into an array
+fn cip25_string(value: String) -> Value {
+    if value.len() <= 64 {
+        return Value::String(value);
+    }
+
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+
+    for c in value.chars() {
+        if chunk.len() + c.len_utf8() > 64 {
+            chunks.push(Value::String(std::mem::take(&mut chunk)));
+        }
+
+        chunk.push(c);
+    }
+
+    chunks.push(Value::String(chunk));
+
+    Value::Array(chunks)
+}
+
+pub fn build_template(config: &AppConfig) -> String {
+    match config.metadata_format() {
+        MetadataFormat::Cip25 => (),
+        MetadataFormat::Erc721 => return build_erc721_template(config),
+        MetadataFormat::Metaplex => return build_metaplex_template(config),
+    }
+
+    let attributes = template_attributes(config);
+
     let mut asset_name = Map::new();
 
     asset_name.insert(
@@ -49,6 +420,10 @@ pub fn build_template(config: &AppConfig) -> String {
         ]),
     );
 
+    if let Some(set) = template_set(config) {
+        asset_name.insert(String::from("set"), Value::String(set));
+    }
+
     asset_name.insert(String::from("attributes"), Value::Object(attributes));
 
     if let Some(extra) = &config.extra {
@@ -69,21 +444,16 @@ pub fn build_template(config: &AppConfig) -> String {
 
 pub fn build_with_attributes(
     attributes: Map<String, Value>,
-    policy_id: Option<String>,
-    name: String,
-    display_name: Option<&String>,
-    extra: Option<Map<String, Value>>,
+    config: &AppConfig,
+    set: Option<&SetConfig>,
     count: usize,
-) -> String {
+    rank: usize,
+) -> anyhow::Result<String> {
+    let token = Token::new(config, set, &attributes, count, rank)?;
+
     let mut asset_name = Map::new();
 
-    asset_name.insert(
-        String::from("name"),
-        Value::String(display_name.map_or_else(
-            || format!("{} #{}", name, count),
-            |display_name| format!("{} #{}", display_name, count),
-        )),
-    );
+    asset_name.insert(String::from("name"), Value::String(token.name.clone()));
 
     asset_name.insert(
         String::from("image"),
@@ -95,36 +465,223 @@ pub fn build_with_attributes(
         Value::String(String::from("image/png")),
     );
 
+    if let Some(description) = token.description {
+        asset_name.insert(String::from("description"), cip25_string(description));
+    }
+
     asset_name.insert(
         String::from("files"),
         json!([
           {
-            "name": display_name.as_ref().map_or_else(
-                || format!("{} #{}", name, count),
-                |display_name| format!("{} #{}", display_name, count),
-            ),
+            "name": token.name,
             "mediaType": "image/png",
             "src": "<ipfs_link>"
           }
         ]),
     );
 
+    if let Some(set) = token.set {
+        asset_name.insert(String::from("set"), Value::String(set));
+    }
+
     asset_name.insert(String::from("attributes"), Value::Object(attributes));
 
-    if let Some(extra) = &extra {
-        asset_name.extend(extra.clone());
+    if let Some(extra) = token.extra {
+        asset_name.extend(extra);
     }
 
-    let policy_id = policy_id.unwrap_or_else(|| String::from("<policy_id>"));
+    let policy_id = config
+        .policy_id
+        .clone()
+        .unwrap_or_else(|| String::from("<policy_id>"));
 
     let json = json!({
       "721": {
         policy_id: {
-          format!("{}{}", name, count): asset_name
+          config.asset_name(count): asset_name
         },
         "version": "1.0"
       }
     });
 
+    Ok(serde_json::to_string_pretty(&json).expect("this should not fail"))
+}
+
+fn build_erc721_template(config: &AppConfig) -> String {
+    let attributes = attribute_list(&template_attributes(config));
+
+    let mut token = Map::new();
+
+    token.insert(
+        String::from("name"),
+        Value::String(String::from("<display_name>")),
+    );
+
+    token.insert(
+        String::from("description"),
+        Value::String(String::from("<description>")),
+    );
+
+    token.insert(
+        String::from("image"),
+        Value::String(String::from("<image_uri>")),
+    );
+
+    if let Some(external_url) = config
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.external_url.as_ref())
+    {
+        token.insert(
+            String::from("external_url"),
+            Value::String(external_url.to_owned()),
+        );
+    }
+
+    if let Some(set) = template_set(config) {
+        token.insert(String::from("set"), Value::String(set));
+    }
+
+    token.insert(String::from("attributes"), json!(attributes));
+
+    serde_json::to_string_pretty(&token).expect("this should not fail")
+}
+
+pub fn build_erc721(
+    attributes: &Map<String, Value>,
+    config: &AppConfig,
+    set: Option<&SetConfig>,
+    image: String,
+    count: usize,
+    rank: usize,
+) -> anyhow::Result<String> {
+    let token = Token::new(config, set, attributes, count, rank)?;
+
+    let attributes = attribute_list(attributes);
+
+    let mut json = Map::new();
+
+    json.insert(String::from("name"), Value::String(token.name));
+
+    json.insert(
+        String::from("description"),
+        Value::String(token.description.unwrap_or_default()),
+    );
+
+    json.insert(String::from("image"), Value::String(image));
+
+    if let Some(external_url) = config
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.external_url.as_ref())
+    {
+        json.insert(
+            String::from("external_url"),
+            Value::String(external_url.to_owned()),
+        );
+    }
+
+    if let Some(set) = token.set {
+        json.insert(String::from("set"), Value::String(set));
+    }
+
+    json.insert(String::from("attributes"), json!(attributes));
+
+    Ok(serde_json::to_string_pretty(&json).expect("this should not fail"))
+}
+
+fn build_metaplex_template(config: &AppConfig) -> String {
+    let attributes = template_attributes(config);
+
+    let token = Token {
+        name: String::from("<display_name>"),
+        description: Some(String::from("<description>")),
+        set: template_set(config),
+        extra: None,
+    };
+
+    metaplex_json(&attributes, config, token, "<number>")
+}
+
+pub fn build_metaplex(
+    attributes: &Map<String, Value>,
+    config: &AppConfig,
+    set: Option<&SetConfig>,
+    count: usize,
+    rank: usize,
+) -> anyhow::Result<String> {
+    let token = Token::new(config, set, attributes, count, rank)?;
+
+    Ok(metaplex_json(attributes, config, token, count))
+}
+
+fn metaplex_json(
+    attributes: &Map<String, Value>,
+    config: &AppConfig,
+    token: Token,
+    number: impl std::fmt::Display,
+) -> String {
+    let metaplex = config.metaplex.as_ref();
+
+    let attributes = attribute_list(attributes);
+
+    let image = format!("{}.png", number);
+
+    let mut json = Map::new();
+
+    json.insert(String::from("name"), Value::String(token.name));
+
+    json.insert(
+        String::from("symbol"),
+        Value::String(metaplex.map(|m| m.symbol.clone()).unwrap_or_default()),
+    );
+
+    json.insert(
+        String::from("description"),
+        Value::String(token.description.unwrap_or_default()),
+    );
+
+    json.insert(
+        String::from("seller_fee_basis_points"),
+        json!(metaplex.map_or(0, |m| m.seller_fee_basis_points)),
+    );
+
+    json.insert(String::from("image"), Value::String(image.clone()));
+
+    if let Some(external_url) = config
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.external_url.as_ref())
+    {
+        json.insert(
+            String::from("external_url"),
+            Value::String(external_url.to_owned()),
+        );
+    }
+
+    if let Some(set) = token.set {
+        json.insert(String::from("set"), Value::String(set));
+    }
+
+    json.insert(String::from("attributes"), json!(attributes));
+
+    if let Some(collection) = metaplex.and_then(|m| m.collection.as_ref()) {
+        json.insert(String::from("collection"), json!(collection));
+    }
+
+    json.insert(
+        String::from("properties"),
+        json!({
+            "files": [
+                {
+                    "uri": image,
+                    "type": "image/png"
+                }
+            ],
+            "category": "image",
+            "creators": metaplex.map(|m| m.creators.clone()).unwrap_or_default()
+        }),
+    );
+
     serde_json::to_string_pretty(&json).expect("this should not fail")
 }
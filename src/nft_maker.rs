@@ -1,13 +1,28 @@
+use std::{
+    fmt,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
 use reqwest::{
-    blocking::Client,
-    header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE},
+    blocking::{Client, RequestBuilder, Response},
+    header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER},
+    StatusCode,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::config::{AppConfig, NftMakerNetwork, NftProjectId};
+use crate::config::{
+    self, AppConfig, NftMakerLocalConfig, NftMakerNetwork, NftMakerProjectConfig, NftProjectId,
+    RetryConfig, APIKEY_ENV,
+};
 
-static MAINNET_URL: &str = "https://api.nft-maker.io";
+static MAINNET_URL: &str = "https://api.nft-maker.io/v2";
 static TESTNET_URL: &str = "https://api-testnet.nft-maker.io/v2";
+static LEGACY_MAINNET_URL: &str = "https://api.nft-maker.io";
+static LEGACY_TESTNET_URL: &str = "https://api-testnet.nft-maker.io";
 
 impl NftMakerNetwork {
     pub fn to_url_string(&self) -> String {
@@ -16,13 +31,167 @@ impl NftMakerNetwork {
             Self::Testnet => String::from(TESTNET_URL),
         }
     }
+
+    pub fn to_legacy_url_string(&self) -> String {
+        match self {
+            Self::Mainnet => String::from(LEGACY_MAINNET_URL),
+            Self::Testnet => String::from(LEGACY_TESTNET_URL),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NftMakerError {
+    /// NFT Maker answered with an error status
+    Api { status: StatusCode, message: String },
+    /// The request never got a usable answer
+    Http(reqwest::Error),
+}
+
+impl fmt::Display for NftMakerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Api { status, message } => write!(f, "{}: {}", status, message),
+            Self::Http(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for NftMakerError {}
+
+impl From<reqwest::Error> for NftMakerError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+/// Decode a successful response or turn the API's error body into an `NftMakerError`
+fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T, NftMakerError> {
+    let status = response.status();
+
+    if status.is_success() {
+        return Ok(response.json()?);
+    }
+
+    Err(api_error(response))
+}
+
+fn api_error(response: Response) -> NftMakerError {
+    let status = response.status();
+
+    let body = response.text().unwrap_or_default();
+
+    let message = serde_json::from_str::<ApiErrorResponse>(&body)
+        .ok()
+        .and_then(|error| error.error_message)
+        .or_else(|| Some(body).filter(|body| !body.trim().is_empty()))
+        .or_else(|| status.canonical_reason().map(String::from))
+        .unwrap_or_default();
+
+    NftMakerError::Api { status, message }
+}
+
+/// How often and how long to wait before retrying rate limited or failed requests
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff for the given attempt, starting at 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+
+        if self.jitter {
+            // keep at least half the delay so retries still back off
+            delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+        } else {
+            delay
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryConfig::default().into()
+    }
+}
+
+impl From<RetryConfig> for RetryPolicy {
+    fn from(config: RetryConfig) -> Self {
+        Self {
+            max_attempts: config.max_attempts.max(1),
+            base_delay: Duration::from_millis(config.base_delay_ms),
+            max_delay: Duration::from_millis(config.max_delay_ms),
+            jitter: config.jitter,
+        }
+    }
+}
+
+/// Spaces requests out evenly, shared by every thread using the client
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / requests_per_second),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().expect("rate limiter lock poisoned");
+
+            let slot = (*next).max(Instant::now());
+
+            *next = slot + self.interval;
+
+            slot
+        };
+
+        let now = Instant::now();
+
+        if slot > now {
+            thread::sleep(slot - now);
+        }
+    }
+}
+
+/// How long the server asked us to wait, given either in seconds or as an HTTP date
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+
+    // a date in the past means we can go right away
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
 }
 
 pub struct NftMakerClient {
-    url: String,
+    base_url: Option<String>,
     network: NftMakerNetwork,
+    legacy: bool,
     apikey: String,
     client: Client,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
 }
 
 impl NftMakerClient {
@@ -31,91 +200,375 @@ impl NftMakerClient {
 
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
 
-        if network.is_testnet() {
-            let bearer = format!("Bearer {}", apikey);
-
-            headers.insert(AUTHORIZATION, bearer.parse().unwrap());
-        }
-
         let client = Client::builder().default_headers(headers).build()?;
 
         Ok(Self {
             apikey,
             client,
             network,
-            url: network.to_url_string(),
+            base_url: None,
+            legacy: false,
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: None,
         })
     }
 
+    /// Send requests to `base_url` instead of the network's default API url
+    pub fn with_base_url(mut self, base_url: Option<String>) -> Self {
+        self.base_url = base_url.map(|base_url| base_url.trim_end_matches('/').to_string());
+
+        self
+    }
+
+    /// Use the legacy API, which takes the API key in the url instead of a bearer header
+    pub fn with_legacy_api(mut self, legacy: bool) -> Self {
+        self.legacy = legacy;
+
+        self
+    }
+
+    fn url(&self) -> String {
+        match &self.base_url {
+            Some(base_url) => base_url.clone(),
+            None if self.legacy => self.network.to_legacy_url_string(),
+            None => self.network.to_url_string(),
+        }
+    }
+
+    fn get(&self, url: String) -> RequestBuilder {
+        if self.legacy {
+            self.client.get(url)
+        } else {
+            self.client
+                .get(url)
+                .header(AUTHORIZATION, format!("Bearer {}", self.apikey))
+        }
+    }
+
+    fn post(&self, url: String) -> RequestBuilder {
+        if self.legacy {
+            self.client.post(url)
+        } else {
+            self.client
+                .post(url)
+                .header(AUTHORIZATION, format!("Bearer {}", self.apikey))
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+
+        self
+    }
+
+    pub fn with_rate_limit(mut self, requests_per_second: Option<f64>) -> Self {
+        self.rate_limiter = requests_per_second
+            .filter(|rps| *rps > 0.0)
+            .map(RateLimiter::new);
+
+        self
+    }
+
+    /// A client set up from a project's `nft_maker` config
+    pub fn from_config(config: &NftMakerLocalConfig) -> anyhow::Result<Self> {
+        if config.apikey.is_empty() {
+            return Err(match &config.profile {
+                Some(profile) => anyhow::anyhow!(
+                    "no NFT Maker API key for profile {}, add it with pix auth --profile {} or set {}",
+                    profile,
+                    profile,
+                    APIKEY_ENV
+                ),
+                None => anyhow::anyhow!("no NFT Maker API key, run pix auth or set {}", APIKEY_ENV),
+            });
+        }
+
+        Ok(Self::new(config.apikey.clone(), config.network)?
+            .with_base_url(config.base_url.clone())
+            .with_legacy_api(config.legacy_api)
+            .with_retry_policy(config.retry.clone().unwrap_or_default().into())
+            .with_rate_limit(config.requests_per_second))
+    }
+
+    /// Send a request, retrying 429s, 5xx errors and dropped connections
+    ///
+    /// Requests that aren't `idempotent` could have gone through before failing, so they are only
+    /// retried when they were rate limited or never reached the server.
+    fn send(&self, request: RequestBuilder, idempotent: bool) -> Result<Response, NftMakerError> {
+        let mut attempt = 1;
+
+        loop {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.wait();
+            }
+
+            let result = request
+                .try_clone()
+                .expect("json requests can always be cloned")
+                .send();
+
+            let wait = match &result {
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    retry_after(response)
+                }
+                Ok(response) if idempotent && response.status().is_server_error() => {
+                    retry_after(response)
+                }
+                Err(e) if e.is_connect() || (idempotent && e.is_timeout()) => None,
+                _ => return Ok(result?),
+            };
+
+            if attempt >= self.retry_policy.max_attempts {
+                return Ok(result?);
+            }
+
+            let wait = wait
+                .map(|wait| wait.min(self.retry_policy.max_delay))
+                .unwrap_or_else(|| self.retry_policy.delay(attempt));
+
+            thread::sleep(wait);
+
+            attempt += 1;
+        }
+    }
+
     pub fn upload_nft(
         &self,
         nft_project_id: &NftProjectId,
-        asset_name: String,
-        mimetype: String,
-        displayname: String,
-        file_from_base64: String,
-        metadata_placeholder: Vec<MetadataPlaceholder>,
-    ) -> anyhow::Result<()> {
-        match self.network {
-            NftMakerNetwork::Mainnet => {
-                let url = format!("{}/UploadNft/{}/{}", self.url, self.apikey, nft_project_id);
-
-                let body = UploadNftRequest {
-                    asset_name: Some(asset_name),
-                    preview_image_nft: NftFile {
-                        mimetype: Some(mimetype),
-                        description: None,
-                        displayname: Some(displayname),
-                        file_from_IPFS: None,
-                        file_froms_url: None,
-                        file_from_base64: Some(file_from_base64),
-                        metadata_placeholder,
-                    },
-                    subfiles: None,
-                    metadata: None,
-                };
-
-                let _ = self.client.post(url).json(&body).send()?;
-            }
-            NftMakerNetwork::Testnet => {
-                let url = format!("{}/UploadNft/{}", self.url, nft_project_id);
+        upload: NftUpload,
+    ) -> Result<UploadNftResponse, NftMakerError> {
+        let NftUpload {
+            asset_name,
+            mimetype,
+            displayname,
+            file_from_base64,
+            metadata_placeholder,
+            metadata,
+            subfiles,
+        } = upload;
 
-                let body = UploadNftRequestV2 {
-                    tokenname: Some(asset_name),
-                    displayname: Some(displayname),
+        let subfiles = Some(subfiles).filter(|subfiles| !subfiles.is_empty());
+
+        let response = if self.legacy {
+            let url = format!(
+                "{}/UploadNft/{}/{}",
+                self.url(),
+                self.apikey,
+                nft_project_id
+            );
+
+            let body = UploadNftRequest {
+                asset_name: Some(asset_name),
+                preview_image_nft: NftFile {
+                    mimetype: Some(mimetype),
                     description: None,
-                    preview_image_nft: NftFileV2 {
-                        mimetype: Some(mimetype),
-                        file_from_base64: Some(file_from_base64),
-                        file_from_IPFS: None,
-                        file_froms_url: None,
-                    },
-                    subfiles: None,
+                    displayname: Some(displayname),
+                    file_from_IPFS: None,
+                    file_froms_url: None,
+                    file_from_base64: Some(file_from_base64),
                     metadata_placeholder,
-                    metadata_override: None,
-                    price_in_lovelace: None,
-                };
+                },
+                subfiles: subfiles.map(|subfiles| {
+                    subfiles
+                        .into_iter()
+                        .map(|subfile| NftFile {
+                            mimetype: Some(subfile.mimetype),
+                            file_from_base64: Some(subfile.file_from_base64),
+                            file_froms_url: None,
+                            file_from_IPFS: None,
+                            description: subfile.description,
+                            displayname: None,
+                            metadata_placeholder: subfile.metadata_placeholder,
+                        })
+                        .collect()
+                }),
+                metadata,
+            };
+
+            self.send(self.post(url).json(&body), false)?
+        } else {
+            let url = format!("{}/UploadNft/{}", self.url(), nft_project_id);
+
+            let body = UploadNftRequestV2 {
+                tokenname: Some(asset_name),
+                displayname: Some(displayname),
+                description: None,
+                preview_image_nft: NftFileV2 {
+                    mimetype: Some(mimetype),
+                    file_from_base64: Some(file_from_base64),
+                    file_from_IPFS: None,
+                    file_froms_url: None,
+                },
+                subfiles: subfiles.map(|subfiles| {
+                    subfiles
+                        .into_iter()
+                        .map(|subfile| NftSubFileV2 {
+                            subfile: NftFileV2 {
+                                mimetype: Some(subfile.mimetype),
+                                file_from_base64: Some(subfile.file_from_base64),
+                                file_froms_url: None,
+                                file_from_IPFS: None,
+                            },
+                            description: subfile.description,
+                            metadata_placeholder: subfile.metadata_placeholder,
+                        })
+                        .collect()
+                }),
+                metadata_placeholder,
+                metadata_override: metadata,
+                price_in_lovelace: None,
+            };
+
+            self.send(self.post(url).json(&body), false)?
+        };
+
+        parse_response(response)
+    }
+
+    /// One page of a project's NFTs in the given state (all, free, reserved, sold or error)
+    pub fn get_nfts(
+        &self,
+        nft_project_id: &NftProjectId,
+        state: &str,
+        count: usize,
+        page: usize,
+    ) -> Result<Vec<NftDetails>, NftMakerError> {
+        let url = if self.legacy {
+            format!(
+                "{}/GetNfts/{}/{}/{}",
+                self.url(),
+                self.apikey,
+                nft_project_id,
+                state
+            )
+        } else {
+            format!(
+                "{}/GetNfts/{}/{}/{}/{}",
+                self.url(),
+                nft_project_id,
+                state,
+                count,
+                page
+            )
+        };
+
+        parse_response(self.send(self.get(url), true)?)
+    }
+
+    /// Every NFT of a project in the given state, going through all the pages
+    pub fn list_nfts(
+        &self,
+        nft_project_id: &NftProjectId,
+        state: &str,
+    ) -> Result<Vec<NftDetails>, NftMakerError> {
+        const PAGE_SIZE: usize = 50;
 
-                let _ = self.client.post(url).json(&body).send()?;
+        // the legacy API doesn't page
+        if self.legacy {
+            return self.get_nfts(nft_project_id, state, PAGE_SIZE, 1);
+        }
+
+        let mut nfts = Vec::new();
+
+        for page in 1.. {
+            let batch = self.get_nfts(nft_project_id, state, PAGE_SIZE, page)?;
+
+            let done = batch.len() < PAGE_SIZE;
+
+            nfts.extend(batch);
+
+            if done {
+                break;
             }
         }
 
-        Ok(())
+        Ok(nfts)
+    }
+
+    pub fn get_nft(
+        &self,
+        nft_project_id: &NftProjectId,
+        nft_id: i32,
+        nft_uid: Option<&str>,
+    ) -> Result<NftDetails, NftMakerError> {
+        let url = match nft_uid {
+            Some(nft_uid) if !self.legacy => {
+                format!("{}/GetNftDetailsById/{}", self.url(), nft_uid)
+            }
+            _ => format!(
+                "{}/GetNftDetailsById/{}/{}/{}",
+                self.url(),
+                self.apikey,
+                nft_project_id,
+                nft_id
+            ),
+        };
+
+        parse_response(self.send(self.get(url), true)?)
+    }
+
+    /// Delete an NFT that hasn't been sold or minted yet
+    pub fn delete_nft(
+        &self,
+        nft_project_id: &NftProjectId,
+        nft_id: i32,
+        nft_uid: Option<&str>,
+    ) -> Result<(), NftMakerError> {
+        let url = match nft_uid {
+            Some(nft_uid) if !self.legacy => format!("{}/DeleteNft/{}", self.url(), nft_uid),
+            _ => format!(
+                "{}/DeleteNft/{}/{}/{}",
+                self.url(),
+                self.apikey,
+                nft_project_id,
+                nft_id
+            ),
+        };
+
+        let response = self.send(self.get(url), true)?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(api_error(response))
+        }
     }
 
     pub fn create_project(
         &self,
         body: &CreateProjectRequest,
-    ) -> anyhow::Result<CreateProjectResponse> {
-        let url = format!("{}/CreateProject/{}", self.url, self.apikey);
+    ) -> Result<CreateProjectResponse, NftMakerError> {
+        let url = if self.legacy {
+            format!("{}/CreateProject/{}", self.url(), self.apikey)
+        } else {
+            format!("{}/CreateProject", self.url())
+        };
 
-        let create_project_response: CreateProjectResponse =
-            self.client.post(url).json(body).send()?.json()?;
-
-        Ok(create_project_response)
+        parse_response(self.send(self.post(url).json(body), false)?)
     }
 }
 
+/// A single NFT to upload, independent of the API version
+#[derive(Debug, Default)]
+pub struct NftUpload {
+    pub asset_name: String,
+    pub mimetype: String,
+    pub displayname: String,
+    pub file_from_base64: String,
+    pub metadata_placeholder: Vec<MetadataPlaceholder>,
+    /// Complete metadata that replaces the project's metadata template
+    pub metadata: Option<String>,
+    pub subfiles: Vec<NftUploadSubfile>,
+}
+
+#[derive(Debug, Default)]
+pub struct NftUploadSubfile {
+    pub mimetype: String,
+    pub file_from_base64: String,
+    pub description: Option<String>,
+    pub metadata_placeholder: Vec<MetadataPlaceholder>,
+}
+
 #[derive(Serialize, Debug)]
 pub struct MetadataPlaceholder {
     pub name: Option<String>,
@@ -187,7 +640,15 @@ pub struct UploadNftResponse {
     pub asset_id: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiErrorResponse {
+    pub result_state: Option<String>,
+    pub error_code: Option<i32>,
+    pub error_message: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct NftDetails {
     pub id: i32,
@@ -196,6 +657,7 @@ pub struct NftDetails {
     pub name: Option<String>,
     pub displayname: Option<String>,
     pub detaildata: Option<String>,
+    #[serde(default)]
     pub minted: bool,
     pub receiveraddress: Option<String>,
     pub selldate: Option<String>, // DateTime
@@ -214,6 +676,22 @@ pub struct NftDetails {
     pub uid: Option<String>,
 }
 
+impl NftDetails {
+    /// free, reserved, sold or minted
+    pub fn status(&self) -> String {
+        if self.minted {
+            String::from("minted")
+        } else {
+            self.state.as_deref().unwrap_or("unknown").to_lowercase()
+        }
+    }
+
+    /// Minted or sold NFTs can no longer be changed
+    pub fn is_locked(&self) -> bool {
+        self.minted || self.status() == "sold"
+    }
+}
+
 #[derive(Deserialize, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateProjectRequest {
@@ -231,21 +709,30 @@ pub struct CreateProjectRequest {
 }
 
 impl CreateProjectRequest {
-    pub fn new(config: &AppConfig, metadata_info: String, expiration_time: String) -> Self {
+    pub fn new(config: &AppConfig, metadata_info: String, project: &NftMakerProjectConfig) -> Self {
         Self {
             projectname: Some(config.name.to_owned()),
-            description: None,
-            projecturl: None,
-            tokenname_prefix: None,
+            description: project.description.clone(),
+            projecturl: project.project_url.clone(),
+            tokenname_prefix: project.tokenname_prefix.clone(),
             policy_expires: true,
-            policy_locks_date_time: Some(expiration_time),
-            payout_walletaddress: None,
-            max_nft_supply: 1,
+            policy_locks_date_time: Some(project.policy_locks_at.clone()),
+            payout_walletaddress: project.payout_wallet.clone(),
+            max_nft_supply: project.max_supply,
             policy: Default::default(),
             metadata: Some(metadata_info),
             address_expiretime: 20,
         }
     }
+
+    /// Create the project with a policy made with `cardano-cli`, locking when its script does
+    pub fn with_policy(mut self, policy: Policy, locks_at: Option<DateTime<Utc>>) -> Self {
+        self.policy = policy;
+        self.policy_expires = locks_at.is_some();
+        self.policy_locks_date_time = locks_at.map(config::format_lock_date);
+
+        self
+    }
 }
 
 #[derive(Deserialize, Debug, Serialize, Default)]
@@ -0,0 +1,119 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+
+use crate::config::{AppConfig, MetadataFormat};
+
+pub struct TokenFiles {
+    pub folder: Option<PathBuf>,
+    pub image: PathBuf,
+    pub attributes: Option<PathBuf>,
+    pub metadata: PathBuf,
+}
+
+impl TokenFiles {
+    pub fn new(config: &AppConfig, output: &Path, number: usize) -> Self {
+        match config.metadata_format() {
+            MetadataFormat::Cip25 => {
+                let name = format!("{}#{}", config.name, number);
+
+                let folder = output.join(&name);
+
+                Self {
+                    image: folder.join(format!("{}.png", name)),
+                    attributes: Some(folder.join(format!("{}.json", name))),
+                    metadata: folder.join("metadata.json"),
+                    folder: Some(folder),
+                }
+            }
+            MetadataFormat::Erc721 => {
+                let json_extension = config
+                    .metadata
+                    .as_ref()
+                    .is_some_and(|metadata| metadata.json_extension);
+
+                let metadata_name = if json_extension {
+                    format!("{}.json", number)
+                } else {
+                    number.to_string()
+                };
+
+                Self {
+                    folder: None,
+                    image: output.join("images").join(format!("{}.png", number)),
+                    attributes: None,
+                    metadata: output.join("metadata").join(metadata_name),
+                }
+            }
+            // rarity.json and the manifest stay out of the Candy Machine asset folder
+            MetadataFormat::Metaplex => Self {
+                folder: None,
+                image: output.join("assets").join(format!("{}.png", number)),
+                attributes: None,
+                metadata: output.join("assets").join(format!("{}.json", number)),
+            },
+        }
+    }
+
+    /// The URI the token metadata should point at for its image
+    pub fn image_uri(config: &AppConfig, number: usize) -> String {
+        let base_uri = config
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.base_uri.as_deref())
+            .unwrap_or("ipfs://<CID>");
+
+        format!("{}/{}.png", base_uri.trim_end_matches('/'), number)
+    }
+}
+
+/// Create the folders shared by every token for the configured format
+pub fn prepare(config: &AppConfig, output: &Path) -> anyhow::Result<()> {
+    fs::create_dir(output)?;
+
+    match config.metadata_format() {
+        MetadataFormat::Cip25 => (),
+        MetadataFormat::Erc721 => {
+            fs::create_dir(output.join("images"))?;
+            fs::create_dir(output.join("metadata"))?;
+        }
+        MetadataFormat::Metaplex => fs::create_dir(output.join("assets"))?,
+    }
+
+    Ok(())
+}
+
+/// The `Name#N/` token folders in the output, sorted by token number
+///
+/// Only the cip25 layout has token folders, the other formats can't be uploaded.
+pub fn local_tokens(config: &AppConfig, output: &Path) -> anyhow::Result<Vec<(usize, PathBuf)>> {
+    let format = config.metadata_format();
+
+    if format != MetadataFormat::Cip25 {
+        return Err(anyhow!(
+            "nft-maker.io needs cip25 output, {} output has no token folders",
+            format
+        ));
+    }
+
+    let mut tokens: Vec<(usize, PathBuf)> = output
+        .read_dir()
+        .with_context(|| format!("{} is not a folder", output.display()))?
+        .map(|dir| dir.unwrap().path())
+        .filter(|path| path.is_dir())
+        .filter_map(|path| {
+            let nft_name = path.file_name()?.to_str()?;
+
+            let (_, number) = nft_name.split_once('#')?;
+
+            Some((number.parse().ok()?, path))
+        })
+        .collect();
+
+    tokens.sort_by_key(|(number, _)| *number);
+
+    Ok(tokens)
+}
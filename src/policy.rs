@@ -0,0 +1,376 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context};
+use blake2::{digest::consts::U28, Blake2b, Digest};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{self, NftMakerNetwork},
+    nft_maker::Policy,
+};
+
+/// A Cardano native script, as written by `cardano-cli`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum NativeScript {
+    Sig {
+        #[serde(rename = "keyHash")]
+        key_hash: String,
+    },
+    All {
+        scripts: Vec<NativeScript>,
+    },
+    Any {
+        scripts: Vec<NativeScript>,
+    },
+    AtLeast {
+        required: u64,
+        scripts: Vec<NativeScript>,
+    },
+    Before {
+        slot: u64,
+    },
+    After {
+        slot: u64,
+    },
+}
+
+impl NativeScript {
+    pub fn parse(json: &str) -> anyhow::Result<Self> {
+        let script: Self = serde_json::from_str(json).context("invalid native script")?;
+
+        script.validate()?;
+
+        Ok(script)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+
+        Self::parse(&json).with_context(|| format!("failed to load {}", path.display()))
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            Self::Sig { key_hash } => {
+                if !is_hash(key_hash) {
+                    return Err(anyhow!("key hash {} should be 56 hex characters", key_hash));
+                }
+            }
+            Self::All { scripts } | Self::Any { scripts } => {
+                scripts.iter().try_for_each(Self::validate)?;
+            }
+            Self::AtLeast { required, scripts } => {
+                if *required as usize > scripts.len() {
+                    return Err(anyhow!(
+                        "atLeast requires {} of only {} scripts",
+                        required,
+                        scripts.len()
+                    ));
+                }
+
+                scripts.iter().try_for_each(Self::validate)?;
+            }
+            Self::Before { .. } | Self::After { .. } => (),
+        }
+
+        Ok(())
+    }
+
+    /// The script's CBOR encoding, as it is hashed into the policy id
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        self.write_cbor(&mut out);
+
+        out
+    }
+
+    fn write_cbor(&self, out: &mut Vec<u8>) {
+        let write_scripts = |out: &mut Vec<u8>, scripts: &[NativeScript]| {
+            write_head(out, ARRAY, scripts.len() as u64);
+
+            for script in scripts {
+                script.write_cbor(out);
+            }
+        };
+
+        match self {
+            Self::Sig { key_hash } => {
+                let bytes = hex::decode(key_hash).expect("key hashes are validated on parse");
+
+                write_head(out, ARRAY, 2);
+                write_head(out, UNSIGNED, 0);
+                write_head(out, BYTES, bytes.len() as u64);
+                out.extend(bytes);
+            }
+            Self::All { scripts } => {
+                write_head(out, ARRAY, 2);
+                write_head(out, UNSIGNED, 1);
+                write_scripts(out, scripts);
+            }
+            Self::Any { scripts } => {
+                write_head(out, ARRAY, 2);
+                write_head(out, UNSIGNED, 2);
+                write_scripts(out, scripts);
+            }
+            Self::AtLeast { required, scripts } => {
+                write_head(out, ARRAY, 3);
+                write_head(out, UNSIGNED, 3);
+                write_head(out, UNSIGNED, *required);
+                write_scripts(out, scripts);
+            }
+            Self::After { slot } => {
+                write_head(out, ARRAY, 2);
+                write_head(out, UNSIGNED, 4);
+                write_head(out, UNSIGNED, *slot);
+            }
+            Self::Before { slot } => {
+                write_head(out, ARRAY, 2);
+                write_head(out, UNSIGNED, 5);
+                write_head(out, UNSIGNED, *slot);
+            }
+        }
+    }
+
+    /// The policy id of the script, blake2b-224 of its CBOR behind the native script tag
+    pub fn policy_id(&self) -> String {
+        let mut bytes = vec![0];
+
+        bytes.extend(self.to_cbor());
+
+        blake2b_224(&bytes)
+    }
+
+    /// The last slot the script can be satisfied in, when it locks at all
+    ///
+    /// `before` locks at the slot before it, `all` at its earliest lock, `any` at its
+    /// latest, and `atLeast` at the latest slot enough of its scripts are still open.
+    pub fn lock_slot(&self) -> Option<u64> {
+        let nth_latest = |required: usize, scripts: &[NativeScript]| {
+            if required == 0 {
+                return None;
+            }
+
+            // scripts that never lock stay open forever
+            let mut locks: Vec<u64> = scripts
+                .iter()
+                .map(|script| script.lock_slot().unwrap_or(u64::MAX))
+                .collect();
+
+            locks.sort_unstable_by(|a, b| b.cmp(a));
+
+            locks
+                .get(required - 1)
+                .copied()
+                .filter(|slot| *slot != u64::MAX)
+        };
+
+        match self {
+            Self::Before { slot } => Some(slot.saturating_sub(1)),
+            Self::Sig { .. } | Self::After { .. } => None,
+            Self::All { scripts } => nth_latest(scripts.len(), scripts),
+            Self::Any { scripts } => nth_latest(1, scripts),
+            Self::AtLeast { required, scripts } => nth_latest(*required as usize, scripts),
+        }
+    }
+
+    /// Every key hash that can sign for the script
+    pub fn key_hashes(&self) -> Vec<&str> {
+        match self {
+            Self::Sig { key_hash } => vec![key_hash.as_str()],
+            Self::All { scripts } | Self::Any { scripts } | Self::AtLeast { scripts, .. } => {
+                scripts.iter().flat_map(Self::key_hashes).collect()
+            }
+            Self::Before { .. } | Self::After { .. } => Vec::new(),
+        }
+    }
+}
+
+const UNSIGNED: u8 = 0;
+const BYTES: u8 = 2;
+const ARRAY: u8 = 4;
+
+/// Write a CBOR major type with its argument in the shortest form
+fn write_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xff => out.extend([major | 24, value as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend((value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend((value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend(value.to_be_bytes());
+        }
+    }
+}
+
+fn blake2b_224(bytes: &[u8]) -> String {
+    hex::encode(Blake2b::<U28>::digest(bytes))
+}
+
+/// A policy id or key hash: 28 bytes as hex
+pub fn is_hash(value: &str) -> bool {
+    value.len() == 56 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// A `cardano-cli` key file
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TextEnvelope {
+    #[serde(rename = "type")]
+    kind: String,
+    cbor_hex: String,
+}
+
+impl TextEnvelope {
+    fn load(path: &Path) -> anyhow::Result<(Self, String)> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+
+        let envelope = serde_json::from_str(&json)
+            .with_context(|| format!("{} is not a cardano-cli key file", path.display()))?;
+
+        Ok((envelope, json))
+    }
+
+    /// The raw key inside the CBOR byte string
+    fn key(&self) -> anyhow::Result<Vec<u8>> {
+        let bytes = hex::decode(&self.cbor_hex).context("cborHex is not hex")?;
+
+        match bytes.split_first() {
+            Some((0x58, [length, key @ ..])) if *length as usize == key.len() => Ok(key.to_vec()),
+            _ => Err(anyhow!("cborHex is not a key")),
+        }
+    }
+}
+
+/// Make sure the configured `policy_id` is the one `script` hashes to
+pub fn check_policy_id(
+    native_script: &NativeScript,
+    script: &Path,
+    policy_id: Option<&str>,
+) -> anyhow::Result<()> {
+    let id = native_script.policy_id();
+
+    match policy_id {
+        Some(policy_id) if policy_id != id => Err(anyhow!(
+            "{} has policy id {} but the config says {}",
+            script.display(),
+            id,
+            policy_id
+        )),
+        Some(_) => Ok(()),
+        None => Err(anyhow!(
+            "set policy_id to {} to use {}",
+            id,
+            script.display()
+        )),
+    }
+}
+
+/// Load a policy made with `cardano-cli` to hand to NFT Maker
+///
+/// The verification key has to be one of the script's signers, and the policy id the
+/// script hashes to has to be the project's `policy_id`.
+pub fn load_policy(
+    script: &Path,
+    skey: &Path,
+    vkey: &Path,
+    policy_id: Option<&str>,
+) -> anyhow::Result<Policy> {
+    let native_script = NativeScript::load(script)?;
+
+    check_policy_id(&native_script, script, policy_id)?;
+
+    let (verification, vkey_json) = TextEnvelope::load(vkey)?;
+
+    if !verification.kind.contains("VerificationKey") {
+        return Err(anyhow!("{} is not a verification key", vkey.display()));
+    }
+
+    let key_hash = blake2b_224(
+        &verification
+            .key()
+            .with_context(|| format!("failed to read {}", vkey.display()))?,
+    );
+
+    if !native_script.key_hashes().contains(&key_hash.as_str()) {
+        return Err(anyhow!(
+            "{} is not a signer of {}",
+            vkey.display(),
+            script.display()
+        ));
+    }
+
+    let (signing, skey_json) = TextEnvelope::load(skey)?;
+
+    if !signing.kind.contains("SigningKey") {
+        return Err(anyhow!("{} is not a signing key", skey.display()));
+    }
+
+    Ok(Policy {
+        policy_id: Some(native_script.policy_id()),
+        private_verifykey: Some(vkey_json),
+        private_signingkey: Some(skey_json),
+        policy_script: Some(fs::read_to_string(script)?),
+    })
+}
+
+/// When a slot starts on NFT Maker's mainnet, or on the preprod testnet it uses
+pub fn slot_time(network: NftMakerNetwork, slot: u64) -> DateTime<Utc> {
+    // slot 0 as if every slot had lasted a second, as they have since Shelley
+    let start = match network {
+        NftMakerNetwork::Mainnet => 1_591_566_291,
+        NftMakerNetwork::Testnet => 1_655_683_200,
+    };
+
+    Utc.timestamp_opt(start + slot as i64, 0).unwrap()
+}
+
+/// When `script` stops minting on `network`, refusing a `policy_locks_at` date that disagrees
+///
+/// Scripts without a `before` slot never lock, so there is no date to check.
+pub fn lock_date(
+    script: &NativeScript,
+    network: NftMakerNetwork,
+    policy_locks_at: Option<&str>,
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let slot = match script.lock_slot() {
+        Some(slot) => slot + 1,
+        None => return Ok(None),
+    };
+
+    let lock = slot_time(network, slot);
+
+    if lock <= Utc::now() {
+        return Err(anyhow!(
+            "the policy script locked at {} (slot {}), nothing can be minted with it anymore",
+            config::format_lock_date(lock),
+            slot
+        ));
+    }
+
+    if let Some(date) = policy_locks_at {
+        if config::parse_lock_date(date)? != lock {
+            return Err(anyhow!(
+                "policy_locks_at {} doesn't match the policy script, which locks at {} (slot {})",
+                date,
+                config::format_lock_date(lock),
+                slot
+            ));
+        }
+    }
+
+    Ok(Some(lock))
+}
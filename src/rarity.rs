@@ -54,4 +54,13 @@ impl Rarity {
             }
         }
     }
+
+    /// The statistical rarity score of a token, the sum of the inverse frequency of its traits
+    pub fn score<'a>(&self, traits: impl IntoIterator<Item = (&'a str, &'a str)>) -> f64 {
+        traits
+            .into_iter()
+            .filter_map(|(layer, name)| self.data.get(layer)?.get(name))
+            .map(|stats| 1.0 / stats.percentage)
+            .sum()
+    }
 }
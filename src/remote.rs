@@ -0,0 +1,189 @@
+use std::{collections::BTreeSet, fmt::Display};
+
+use anyhow::anyhow;
+
+use crate::{
+    config::{AppConfig, NftProjectId},
+    journal::{Journal, JournalEntry},
+    nft_maker::{NftDetails, NftMakerClient},
+};
+
+/// How a token compares between the local output and NFT Maker
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// Uploaded and matching what the journal recorded
+    Ok,
+    /// Generated locally but not on NFT Maker
+    Missing,
+    /// On NFT Maker but not in the local output
+    Extra,
+    /// Re-rendered since it was uploaded, or on NFT Maker with a different IPFS hash than the one
+    /// recorded at upload
+    Mismatch,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Status::*;
+
+        match self {
+            Ok => write!(f, "ok"),
+            Missing => write!(f, "missing"),
+            Extra => write!(f, "extra"),
+            Mismatch => write!(f, "mismatch"),
+        }
+    }
+}
+
+pub struct Row {
+    pub number: Option<usize>,
+    pub nft: Option<NftDetails>,
+    pub status: Status,
+}
+
+/// A token in the local output
+pub struct Local {
+    pub number: usize,
+    /// The image hash from the manifest, if there is one
+    pub image_hash: Option<String>,
+}
+
+/// The token number of a remote NFT, from its asset name
+pub fn token_number(config: &AppConfig, nft: &NftDetails) -> Option<usize> {
+    [&nft.name, &nft.assetname]
+        .into_iter()
+        .flatten()
+        .find_map(|asset_name| config.token_number(asset_name))
+}
+
+/// Find the remote NFT for a token, by the id recorded at upload or else by name
+pub fn find<'a>(
+    config: &AppConfig,
+    journal: &Journal,
+    nfts: &'a [NftDetails],
+    number: usize,
+) -> Option<&'a NftDetails> {
+    let by_id = match journal.get(number) {
+        Some(JournalEntry::Uploaded { nft_id, .. }) => nfts.iter().find(|nft| nft.id == *nft_id),
+        _ => None,
+    };
+
+    by_id.or_else(|| {
+        nfts.iter()
+            .find(|nft| token_number(config, nft) == Some(number))
+    })
+}
+
+/// Compare the local tokens and upload journal with the NFTs on the server
+pub fn diff(
+    config: &AppConfig,
+    local: &[Local],
+    journal: &Journal,
+    nfts: &[NftDetails],
+) -> Vec<Row> {
+    let mut rows = Vec::new();
+
+    let mut matched = BTreeSet::new();
+
+    for token in local {
+        let number = token.number;
+
+        let row = match find(config, journal, nfts, number) {
+            Some(nft) => {
+                matched.insert(nft.id);
+
+                let (ipfs_hash, image_hash) = match journal.get(number) {
+                    Some(JournalEntry::Uploaded {
+                        ipfs_hash,
+                        image_hash,
+                        ..
+                    }) => (ipfs_hash.as_deref(), image_hash.as_deref()),
+                    _ => (None, None),
+                };
+
+                let differs = |recorded: Option<&str>, current: Option<&str>| matches!((recorded, current), (Some(recorded), Some(current)) if recorded != current);
+
+                let status = if differs(image_hash, token.image_hash.as_deref())
+                    || differs(ipfs_hash, nft.ipfshash.as_deref())
+                {
+                    Status::Mismatch
+                } else {
+                    Status::Ok
+                };
+
+                Row {
+                    number: Some(number),
+                    nft: Some(nft.clone()),
+                    status,
+                }
+            }
+            None => Row {
+                number: Some(number),
+                nft: None,
+                status: Status::Missing,
+            },
+        };
+
+        rows.push(row);
+    }
+
+    for nft in nfts.iter().filter(|nft| !matched.contains(&nft.id)) {
+        rows.push(Row {
+            number: token_number(config, nft),
+            nft: Some(nft.clone()),
+            status: Status::Extra,
+        });
+    }
+
+    rows
+}
+
+/// Delete tokens from NFT Maker and forget them in the journal
+///
+/// Nothing is deleted when one of the tokens was sold or minted.
+/// Returns the tokens that were deleted, tokens NFT Maker doesn't have are only
+/// dropped from the journal.
+pub fn delete(
+    client: &NftMakerClient,
+    nft_project_id: &NftProjectId,
+    config: &AppConfig,
+    journal: &mut Journal,
+    numbers: &[usize],
+) -> anyhow::Result<Vec<usize>> {
+    let nfts = client.list_nfts(nft_project_id, "all")?;
+
+    let found: Vec<(usize, &NftDetails)> = numbers
+        .iter()
+        .filter_map(|&number| Some((number, find(config, journal, &nfts, number)?)))
+        .collect();
+
+    let locked: Vec<String> = found
+        .iter()
+        .filter(|(_, nft)| nft.is_locked())
+        .map(|(number, nft)| format!("{} ({})", number, nft.status()))
+        .collect();
+
+    if !locked.is_empty() {
+        return Err(anyhow!(
+            "refusing to delete sold or minted tokens: {}",
+            locked.join(", ")
+        ));
+    }
+
+    let mut deleted = Vec::new();
+
+    for (number, nft) in found {
+        client.delete_nft(nft_project_id, nft.id, nft.uid.as_deref())?;
+
+        // forget it straight away in case a later delete fails
+        journal.remove(number)?;
+
+        deleted.push(number);
+    }
+
+    for &number in numbers {
+        journal.remove(number)?;
+    }
+
+    Ok(deleted)
+}
@@ -0,0 +1,48 @@
+use anyhow::anyhow;
+use jsonschema::JSONSchema;
+use schemars::{gen::SchemaSettings, schema::RootSchema};
+use serde_json::Value;
+
+use crate::config::{self, AppConfig};
+
+/// The JSON Schema of a project config
+pub fn schema() -> RootSchema {
+    // optional fields are left out rather than set to null, and a plain
+    // reference reports the errors inside it instead of a failed anyOf
+    SchemaSettings::draft07()
+        .with(|settings| settings.option_add_null_type = false)
+        .into_generator()
+        .into_root_schema_for::<AppConfig>()
+}
+
+/// Check a config against the schema, reporting every field that doesn't fit
+pub fn validate(config: &Value) -> anyhow::Result<()> {
+    let schema = serde_json::to_value(schema())?;
+
+    let compiled = JSONSchema::compile(&schema).map_err(|e| anyhow!("invalid schema: {}", e))?;
+
+    // the schema has no null type, but an explicit null is as good as leaving a field out
+    let mut config = config.clone();
+
+    config::strip_nulls(&mut config);
+
+    let result = compiled.validate(&config);
+
+    if let Err(errors) = result {
+        let problems: Vec<String> = errors
+            .map(|error| {
+                let path = error.instance_path.to_string();
+
+                format!(
+                    "at {}: {}",
+                    if path.is_empty() { "/" } else { &path },
+                    error
+                )
+            })
+            .collect();
+
+        return Err(anyhow!("{}", problems.join("\n")));
+    }
+
+    Ok(())
+}
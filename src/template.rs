@@ -0,0 +1,285 @@
+use anyhow::{anyhow, Context};
+use serde_json::{Map, Value};
+
+use crate::layers::NONE;
+
+/// A parsed metadata template
+///
+/// Templates are plain strings with `{{...}}` tags:
+///
+/// - `{{eyes}}` inserts a variable
+/// - `{{number:05}}` pads a variable with zeros (or spaces without the leading `0`)
+/// - `{{eyes|plain}}` falls back to a default when the variable is missing or `None`
+/// - `{{#if eyes}}...{{else}}...{{/if}}` renders a block when the variable is set,
+///   `{{#if eyes == "laser"}}` and `{{#if eyes != "laser"}}` compare it to a value
+/// - `\{{` writes a literal `{{`
+#[derive(Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Var {
+        name: String,
+        pad: Option<(char, usize)>,
+        default: Option<String>,
+    },
+    If {
+        condition: Condition,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+#[derive(Debug)]
+enum Condition {
+    Set(String),
+    Eq(String, String),
+    Ne(String, String),
+}
+
+enum Tag {
+    Var(Node),
+    If(Condition),
+    Else,
+    EndIf,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut rest = source;
+
+        let (nodes, end) = parse_nodes(&mut rest)
+            .with_context(|| format!("failed to parse template `{}`", source))?;
+
+        match end {
+            None => Ok(Self { nodes }),
+            Some(_) => Err(anyhow!(
+                "failed to parse template `{}`\nReason: unexpected {{{{else}}}} or {{{{/if}}}}",
+                source
+            )),
+        }
+    }
+
+    /// Every variable the template refers to
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+
+        collect_variables(&self.nodes, &mut names);
+
+        names
+    }
+
+    pub fn render(&self, vars: &Map<String, Value>) -> String {
+        let mut out = String::new();
+
+        render_nodes(&self.nodes, vars, &mut out);
+
+        out
+    }
+}
+
+/// Parse and render a template in one go
+pub fn render(source: &str, vars: &Map<String, Value>) -> anyhow::Result<String> {
+    Ok(Template::parse(source)?.render(vars))
+}
+
+fn parse_nodes(rest: &mut &str) -> anyhow::Result<(Vec<Node>, Option<Tag>)> {
+    let mut nodes = Vec::new();
+
+    loop {
+        let start = match rest.find("{{") {
+            // `\{{` is a literal `{{`
+            Some(start) if rest[..start].ends_with('\\') => {
+                nodes.push(Node::Text(format!("{}{{{{", &rest[..start - 1])));
+
+                *rest = &rest[start + 2..];
+
+                continue;
+            }
+            Some(start) => start,
+            None => {
+                if !rest.is_empty() {
+                    nodes.push(Node::Text(rest.to_string()));
+                }
+
+                *rest = "";
+
+                return Ok((nodes, None));
+            }
+        };
+
+        if start > 0 {
+            nodes.push(Node::Text(rest[..start].to_string()));
+        }
+
+        let end = rest[start..]
+            .find("}}")
+            .map(|end| start + end)
+            .context("unclosed `{{`")?;
+
+        let tag = parse_tag(&rest[start + 2..end])?;
+
+        *rest = &rest[end + 2..];
+
+        match tag {
+            Tag::Var(node) => nodes.push(node),
+            Tag::If(condition) => {
+                let (then, end) = parse_nodes(rest)?;
+
+                let otherwise = match end {
+                    Some(Tag::EndIf) => Vec::new(),
+                    Some(Tag::Else) => match parse_nodes(rest)? {
+                        (otherwise, Some(Tag::EndIf)) => otherwise,
+                        _ => return Err(anyhow!("missing `{{{{/if}}}}`")),
+                    },
+                    _ => return Err(anyhow!("missing `{{{{/if}}}}`")),
+                };
+
+                nodes.push(Node::If {
+                    condition,
+                    then,
+                    otherwise,
+                });
+            }
+            end @ (Tag::Else | Tag::EndIf) => return Ok((nodes, Some(end))),
+        }
+    }
+}
+
+fn parse_tag(tag: &str) -> anyhow::Result<Tag> {
+    let tag = tag.trim();
+
+    if let Some(condition) = tag.strip_prefix("#if") {
+        return parse_condition(condition.trim()).map(Tag::If);
+    }
+
+    match tag {
+        "else" => return Ok(Tag::Else),
+        "/if" => return Ok(Tag::EndIf),
+        "" => return Err(anyhow!("empty `{{{{}}}}`")),
+        _ => (),
+    }
+
+    let (expr, default) = match tag.split_once('|') {
+        Some((expr, default)) => (expr.trim(), Some(default.trim().to_string())),
+        None => (tag, None),
+    };
+
+    let (name, pad) = match expr.rsplit_once(':') {
+        Some((name, spec)) if !spec.is_empty() && spec.chars().all(|c| c.is_ascii_digit()) => {
+            let fill = if spec.starts_with('0') { '0' } else { ' ' };
+
+            (name.trim(), Some((fill, spec.parse()?)))
+        }
+        _ => (expr, None),
+    };
+
+    Ok(Tag::Var(Node::Var {
+        name: name.to_string(),
+        pad,
+        default,
+    }))
+}
+
+fn parse_condition(condition: &str) -> anyhow::Result<Condition> {
+    let parse_value = |value: &str| {
+        let value = value.trim();
+
+        value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value)
+            .to_string()
+    };
+
+    if let Some((name, value)) = condition.split_once("!=") {
+        Ok(Condition::Ne(name.trim().to_string(), parse_value(value)))
+    } else if let Some((name, value)) = condition.split_once("==") {
+        Ok(Condition::Eq(name.trim().to_string(), parse_value(value)))
+    } else if condition.is_empty() {
+        Err(anyhow!("`{{{{#if}}}}` needs a condition"))
+    } else {
+        Ok(Condition::Set(condition.to_string()))
+    }
+}
+
+fn collect_variables<'a>(nodes: &'a [Node], names: &mut Vec<&'a str>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => (),
+            Node::Var { name, .. } => names.push(name),
+            Node::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                match condition {
+                    Condition::Set(name) | Condition::Eq(name, _) | Condition::Ne(name, _) => {
+                        names.push(name)
+                    }
+                }
+
+                collect_variables(then, names);
+                collect_variables(otherwise, names);
+            }
+        }
+    }
+}
+
+fn render_nodes(nodes: &[Node], vars: &Map<String, Value>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var { name, pad, default } => {
+                let value = lookup(vars, name)
+                    .or_else(|| default.clone())
+                    .unwrap_or_default();
+
+                match pad {
+                    Some((fill, width)) if value.chars().count() < *width => {
+                        let padding = width - value.chars().count();
+
+                        out.extend(std::iter::repeat_n(*fill, padding));
+                        out.push_str(&value);
+                    }
+                    _ => out.push_str(&value),
+                }
+            }
+            Node::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let matches = match condition {
+                    Condition::Set(name) => lookup(vars, name).is_some(),
+                    Condition::Eq(name, value) => raw(vars, name).as_ref() == Some(value),
+                    Condition::Ne(name, value) => raw(vars, name).as_ref() != Some(value),
+                };
+
+                render_nodes(if matches { then } else { otherwise }, vars, out);
+            }
+        }
+    }
+}
+
+fn raw(vars: &Map<String, Value>, name: &str) -> Option<String> {
+    match vars.get(name)? {
+        Value::Null => None,
+        Value::String(value) => Some(value.to_owned()),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Look up a variable as a string, treating empty values, `false` and the `None` trait as unset
+///
+/// `None` is reserved for the trait of layers that can be left out, so no real trait has it.
+fn lookup(vars: &Map<String, Value>, name: &str) -> Option<String> {
+    match vars.get(name)? {
+        Value::Bool(false) => None,
+        Value::String(value) if value == NONE => None,
+        _ => raw(vars, name).filter(|value| !value.is_empty()),
+    }
+}
@@ -1,7 +1,11 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
 
 use anyhow::Context;
 use image::{imageops, GenericImage, GenericImageView};
+use sha2::{Digest, Sha256};
 
 pub fn merge<I, J>(bottom: &mut I, top: &J)
 where
@@ -19,3 +23,61 @@ pub fn clean(output: &Path) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// `path` relative to the folder `base`, both absolute
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    // paths on different drives can't be relative to each other
+    if path.components().next() != base.components().next() {
+        return path.to_path_buf();
+    }
+
+    let mut path = path.components().peekable();
+    let mut base = base.components().peekable();
+
+    while let (Some(a), Some(b)) = (path.peek(), base.peek()) {
+        if a != b {
+            break;
+        }
+
+        path.next();
+        base.next();
+    }
+
+    base.map(|_| Component::ParentDir).chain(path).collect()
+}
+
+pub fn sha256(bytes: impl AsRef<[u8]>) -> String {
+    hex::encode(Sha256::digest(bytes.as_ref()))
+}
+
+pub fn sha256_file(path: &Path) -> anyhow::Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+
+    Ok(sha256(bytes))
+}
+
+/// Guess a file's mime type from its extension
+pub fn mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+
+    let mime_type = match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "glb" => "model/gltf-binary",
+        "gltf" => "model/gltf+json",
+        "html" => "text/html",
+        "pdf" => "application/pdf",
+        _ => return None,
+    };
+
+    Some(mime_type)
+}
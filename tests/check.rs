@@ -0,0 +1,135 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use image::RgbaImage;
+
+use pix::{
+    check,
+    cli::Mode,
+    config::{AppConfig, IfTrait, LayerConfig, SetConfig},
+};
+
+fn png(path: &Path, size: u32) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+    RgbaImage::new(size, size).save(path).unwrap();
+}
+
+fn layer(name: &str) -> LayerConfig {
+    LayerConfig {
+        name: String::from(name),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn check_reports_every_problem_with_its_path() {
+    let dir = std::env::temp_dir().join(format!("pix-check-{}", std::process::id()));
+
+    let images = dir.join("images");
+
+    png(&images.join("background/blue#10.png"), 8);
+    png(&images.join("background/red.png"), 8);
+    png(&images.join("eyes/laser#5.png"), 16);
+
+    let config = AppConfig {
+        name: String::from("Bear"),
+        mode: Mode::Advanced,
+        amount: 1,
+        path: images.clone(),
+        layers: vec![
+            layer("background"),
+            LayerConfig {
+                exclude_if_traits: Some(vec![
+                    IfTrait {
+                        layer: String::from("background"),
+                        traits: vec![String::from("green")],
+                    },
+                    IfTrait {
+                        layer: String::from("hat"),
+                        traits: vec![String::from("cap")],
+                    },
+                ]),
+                ..layer("eyes")
+            },
+            layer("hat"),
+        ],
+        ..Default::default()
+    };
+
+    let problems: Vec<String> = check::check(&config, Path::new("pix.json"))
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    let expected = [
+        format!(
+            "{}: missing `#weight`",
+            images.join("background/red.png").display()
+        ),
+        format!(
+            "{}: image is 16x16 but {} is 8x8",
+            images.join("eyes/laser#5.png").display(),
+            images.join("background/blue#10.png").display()
+        ),
+        format!(
+            "{}: missing folder for layer hat",
+            images.join("hat").display()
+        ),
+        String::from("pix.json: layer eyes excludes unknown trait green of layer background"),
+        String::from("pix.json: layer eyes can only exclude traits of layers before it, not hat"),
+    ];
+
+    assert_eq!(problems, expected);
+}
+
+#[test]
+fn check_uses_each_sets_own_layers() {
+    let dir = std::env::temp_dir().join(format!("pix-check-sets-{}", std::process::id()));
+
+    let images = dir.join("images");
+
+    png(&images.join("Bear/background/blue#10.png"), 8);
+    png(&images.join("Robot/antenna/long#5.png"), 8);
+
+    let config = AppConfig {
+        name: String::from("Bear"),
+        mode: Mode::Advanced,
+        amount: 2,
+        path: images.clone(),
+        sets: Some(vec![
+            SetConfig {
+                name: String::from("Bear"),
+                amount: 1,
+                ..Default::default()
+            },
+            SetConfig {
+                name: String::from("Robot"),
+                amount: 1,
+                layers: Some(vec![layer("antenna"), layer("background")]),
+                none: Some(BTreeMap::from([(String::from("eyes"), 10)])),
+                ..Default::default()
+            },
+        ]),
+        layers: vec![layer("background")],
+        ..Default::default()
+    };
+
+    let problems: Vec<String> = check::check(&config, Path::new("pix.json"))
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    let expected = [
+        String::from("pix.json: set Robot overrides none for unknown layer eyes"),
+        format!(
+            "{}: missing folder for layer background",
+            images.join("Robot/background").display()
+        ),
+    ];
+
+    assert_eq!(problems, expected);
+}
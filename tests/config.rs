@@ -0,0 +1,248 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde_json::{json, Map, Value};
+
+use pix::{
+    config::{AppConfig, ConfigFormat, LayerConfig, SetConfig, SubfileConfig},
+    nft_maker::NftMakerClient,
+};
+
+fn round_trip(format: ConfigFormat, value: &Value) -> Map<String, Value> {
+    let contents = format.to_string(value).unwrap();
+
+    format.parse(&contents).unwrap()
+}
+
+#[test]
+fn formats_round_trip_a_config() {
+    let config = serde_json::to_value(AppConfig::default()).unwrap();
+
+    for format in [ConfigFormat::Json, ConfigFormat::Yaml, ConfigFormat::Toml] {
+        assert_eq!(Value::Object(round_trip(format, &config)), config);
+    }
+}
+
+#[test]
+fn toml_leaves_out_nulls() {
+    let config = json!({ "name": "Bear", "extra": { "twitter": null, "website": "bear.io" } });
+
+    let map = round_trip(ConfigFormat::Toml, &config);
+
+    assert_eq!(map["extra"], json!({ "website": "bear.io" }));
+}
+
+#[test]
+fn format_comes_from_the_extension() {
+    let format = |path: &str| ConfigFormat::from_path(Path::new(path)).ok();
+
+    assert_eq!(format("pix.json"), Some(ConfigFormat::Json));
+    assert_eq!(format("pix.yml"), Some(ConfigFormat::Yaml));
+    assert_eq!(format("bears/pix.toml"), Some(ConfigFormat::Toml));
+    assert_eq!(format("pix.ini"), None);
+}
+
+#[test]
+fn sets_override_layers_and_none() {
+    let layer = |name: &str, none: Option<u32>| LayerConfig {
+        name: String::from(name),
+        none,
+        ..Default::default()
+    };
+
+    let defaults = [layer("background", None), layer("hat", Some(5))];
+
+    let bear = SetConfig {
+        name: String::from("Bear"),
+        none: Some(BTreeMap::from([(String::from("hat"), 50)])),
+        ..Default::default()
+    };
+
+    let robot = SetConfig {
+        name: String::from("Robot"),
+        layers: Some(vec![layer("antenna", None), layer("background", None)]),
+        ..Default::default()
+    };
+
+    let summary = |layers: Vec<LayerConfig>| -> Vec<(String, Option<u32>)> {
+        layers
+            .into_iter()
+            .map(|layer| (layer.name, layer.none))
+            .collect()
+    };
+
+    assert_eq!(
+        summary(bear.layers(&defaults)),
+        [
+            (String::from("background"), None),
+            (String::from("hat"), Some(50))
+        ]
+    );
+    assert_eq!(
+        summary(robot.layers(&defaults)),
+        [
+            (String::from("antenna"), None),
+            (String::from("background"), None)
+        ]
+    );
+}
+
+#[test]
+fn set_attribute_needs_sets_and_a_free_name() {
+    let set = || SetConfig {
+        name: String::from("Bear"),
+        amount: 1,
+        ..Default::default()
+    };
+
+    let config = |set_attribute: &str, sets: Option<Vec<SetConfig>>| AppConfig {
+        set_attribute: Some(String::from(set_attribute)),
+        sets,
+        layers: vec![LayerConfig {
+            name: String::from("hat"),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    assert!(config("Set", None).validate_sets().is_err());
+    assert!(config("hat", Some(vec![set()])).validate_sets().is_err());
+    assert!(config("Set", Some(vec![set()])).validate_sets().is_ok());
+
+    let grouped = |set_attribute: &str| AppConfig {
+        layers: vec![LayerConfig {
+            name: String::from("hat"),
+            group: Some(String::from("head.top")),
+            ..Default::default()
+        }],
+        ..config(set_attribute, Some(vec![set()]))
+    };
+
+    assert!(grouped("head").validate_sets().is_err());
+    assert!(grouped("top").validate_sets().is_ok());
+}
+
+fn bear_config() -> Value {
+    let mut config = serde_json::to_value(AppConfig {
+        name: String::from("Bear"),
+        ..Default::default()
+    })
+    .unwrap();
+
+    // not in alphabetical order, to catch a format that sorts maps
+    config["extra"] = json!({ "website": "bear.io", "twitter": "@bear", "discord": "bear" });
+
+    config
+}
+
+#[test]
+fn loading_keeps_the_order_of_maps() {
+    let dir = std::env::temp_dir().join(format!("pix-config-order-{}", std::process::id()));
+
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for format in [ConfigFormat::Json, ConfigFormat::Yaml, ConfigFormat::Toml] {
+        let path = dir.join(format!("pix.{}", format.extension()));
+
+        std::fs::write(&path, format.to_string(&bear_config()).unwrap()).unwrap();
+
+        let config = AppConfig::new(path.to_str().unwrap()).unwrap();
+
+        let keys: Vec<&String> = config.extra.as_ref().unwrap().keys().collect();
+
+        assert_eq!(keys, ["website", "twitter", "discord"], "{:?}", format);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn insert_adds_a_key_and_keeps_comments() {
+    let nft_maker = json!({ "network": "testnet", "nft_project_id": 7 });
+
+    for format in [ConfigFormat::Json, ConfigFormat::Yaml, ConfigFormat::Toml] {
+        let mut contents = format.to_string(&bear_config()).unwrap();
+
+        if format != ConfigFormat::Json {
+            contents = format!("# my bears\n{}", contents);
+        }
+
+        let inserted = format
+            .insert(&contents, "nft_maker", nft_maker.clone())
+            .unwrap();
+
+        let map = format.parse(&inserted).unwrap();
+
+        assert_eq!(map["nft_maker"], nft_maker, "{:?}", format);
+        assert_eq!(map["name"], "Bear", "{:?}", format);
+        assert_eq!(map["extra"]["discord"], "bear", "{:?}", format);
+
+        if format != ConfigFormat::Json {
+            assert!(inserted.starts_with("# my bears\n"), "{:?}", format);
+        }
+    }
+}
+
+#[test]
+fn toml_leaves_out_nulls_in_arrays() {
+    let config = json!({ "extra": { "tags": ["cute", null, "fluffy"] } });
+
+    let map = round_trip(ConfigFormat::Toml, &config);
+
+    assert_eq!(map["extra"], json!({ "tags": ["cute", "fluffy"] }));
+}
+
+#[test]
+fn subfiles_are_found_in_their_folder_or_the_token_folder() {
+    let subfile = SubfileConfig {
+        suffix: String::from(".mp4"),
+        ..Default::default()
+    };
+
+    let config = Path::new("bears/pix.json");
+    let token = Path::new("bears/output/Bear#1");
+
+    assert_eq!(
+        subfile.path(config, token),
+        Path::new("bears/output/Bear#1/Bear#1.mp4")
+    );
+
+    let subfile = SubfileConfig {
+        folder: Some("animations".into()),
+        ..subfile
+    };
+
+    assert_eq!(
+        subfile.path(config, token),
+        Path::new("bears/animations/Bear#1.mp4")
+    );
+}
+
+#[test]
+fn unknown_profiles_only_matter_to_the_client() {
+    let dir = std::env::temp_dir().join(format!("pix-config-profile-{}", std::process::id()));
+
+    fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("pix.json");
+
+    let mut config = serde_json::to_value(AppConfig::default()).unwrap();
+
+    config["profile"] = json!("pix-test-missing-profile");
+    config["nft_maker"] = json!({ "network": "testnet", "nft_project_id": 1 });
+
+    fs::write(&path, config.to_string()).unwrap();
+
+    let config = AppConfig::new(path.to_str().unwrap());
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    let config = config.unwrap();
+
+    let error = NftMakerClient::from_config(config.nft_maker.as_ref().unwrap())
+        .err()
+        .unwrap();
+
+    assert!(error
+        .to_string()
+        .contains("no NFT Maker API key for profile pix-test-missing-profile"));
+}
@@ -0,0 +1,154 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use image::RgbaImage;
+use serde_json::{json, Map, Value};
+
+use pix::{config::AppConfig, metadata};
+
+fn png(path: &Path) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+    RgbaImage::new(4, 4).save(path).unwrap();
+}
+
+fn read(path: &Path) -> Value {
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+/// Generate a Bear set of 3 and a Robot set of 7 in a new project, returning its folder
+fn gen(name: &str, set_attribute: Option<&str>) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pix-gen-{}-{}", name, std::process::id()));
+
+    let _ = fs::remove_dir_all(&dir);
+
+    for set in ["Bear", "Robot"] {
+        for layer in ["background", "eyes", "hat"] {
+            for name in ["blue#10", "red#20"] {
+                png(&dir.join(format!("images/{}/{}/{}.png", set, layer, name)));
+            }
+        }
+    }
+
+    let mut config = json!({
+        "name": "Bear",
+        "mode": "advanced",
+        "amount": 10,
+        "tolerance": 50,
+        "path": "images",
+        "policy_id": "ab".repeat(28),
+        "sets": [{ "name": "Bear", "amount": 3 }, { "name": "Robot", "amount": 7 }],
+        "layers": [{ "name": "background" }, { "name": "eyes" }, { "name": "hat" }]
+    });
+
+    if let Some(set_attribute) = set_attribute {
+        config["set_attribute"] = json!(set_attribute);
+    }
+
+    fs::write(dir.join("pix.json"), config.to_string()).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_pix"))
+        .args(["gen", "--seed", "7"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+
+    dir
+}
+
+#[test]
+fn set_attribute_comes_first_and_counts_towards_rarity() {
+    let dir = gen("attribute", Some("Set"));
+
+    let config = AppConfig::new(dir.join("pix.json").to_str().unwrap()).unwrap();
+
+    let template: Value = serde_json::from_str(&metadata::build_template(&config)).unwrap();
+
+    let template = &template["721"]["<policy_id>"]["<asset_name>"]["attributes"];
+
+    assert_eq!(template["Set"], "<attribute0>");
+
+    let mut sets = Map::new();
+
+    for number in 0..10 {
+        let token = dir.join(format!("output/Bear#{}/Bear#{}.json", number, number));
+
+        let attributes = read(&token);
+        let attributes = attributes.as_object().unwrap();
+
+        // the set is the first attribute, and so the first placeholder value
+        assert_eq!(attributes.keys().next().map(String::as_str), Some("Set"));
+
+        let set = &metadata::placeholder_values(attributes)[0];
+
+        assert_eq!(Value::String(set.clone()), attributes["Set"]);
+
+        let count = sets.entry(set.clone()).or_insert(json!(0));
+
+        *count = json!(count.as_u64().unwrap() + 1);
+    }
+
+    assert_eq!(Value::Object(sets), json!({ "Bear": 3, "Robot": 7 }));
+
+    // counted against the whole collection, not each set
+    let rarity = read(&dir.join("output/rarity.json"));
+
+    assert_eq!(
+        rarity["Set"]["Bear"],
+        json!({ "amount": 3, "percentage": 0.3 })
+    );
+    assert_eq!(
+        rarity["Set"]["Robot"],
+        json!({ "amount": 7, "percentage": 0.7 })
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sets_are_recorded_without_set_attribute() {
+    let dir = gen("plain", None);
+
+    let config = AppConfig::new(dir.join("pix.json").to_str().unwrap()).unwrap();
+
+    let template: Value = serde_json::from_str(&metadata::build_template(&config)).unwrap();
+
+    assert_eq!(
+        template["721"]["<policy_id>"]["<asset_name>"]["set"],
+        "<set>"
+    );
+
+    let mut sets = Vec::new();
+
+    for number in 0..10 {
+        let metadata = read(&dir.join(format!("output/Bear#{}/metadata.json", number)));
+
+        let asset = &metadata["721"]["ab".repeat(28)][format!("Bear{}", number)];
+
+        // next to the attributes, not in them
+        assert!(asset["attributes"].get("set").is_none());
+
+        sets.push(asset["set"].as_str().unwrap().to_owned());
+    }
+
+    assert_eq!(sets.iter().filter(|set| *set == "Bear").count(), 3);
+    assert_eq!(sets.iter().filter(|set| *set == "Robot").count(), 7);
+
+    let rarity = read(&dir.join("output/rarity.json"));
+
+    assert_eq!(
+        rarity["set"]["Bear"],
+        json!({ "amount": 3, "percentage": 0.3 })
+    );
+    assert_eq!(
+        rarity["set"]["Robot"],
+        json!({ "amount": 7, "percentage": 0.7 })
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
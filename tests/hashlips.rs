@@ -0,0 +1,236 @@
+use std::fs;
+
+use image::RgbaImage;
+
+use pix::hashlips::{self, HashLipsConfig};
+
+const CONFIG: &str = r#"
+const { MODE } = require(`${basePath}/constants/blend_mode.js`);
+
+const namePrefix = "Based Bears";
+const description = 'Bears, on chain';
+
+// the bears come first
+const layerConfigurations = [
+  {
+    growEditionSizeTo: 4,
+    layersOrder: [
+      { name: "Background" },
+      { name: "Eyes", options: { displayName: "Eye Color", blend: MODE.multiply, opacity: 0.5 } },
+    ],
+  },
+  {
+    growEditionSizeTo: 6,
+    layersOrder: [{ name: "Background" }, /* robots have no eyes */],
+  },
+];
+
+const uniqueDnaTorrance = 10000;
+"#;
+
+#[test]
+fn parses_a_hashlips_config() {
+    let config = HashLipsConfig::parse(CONFIG).unwrap();
+
+    assert_eq!(config.name_prefix.as_deref(), Some("Based Bears"));
+    assert_eq!(config.description.as_deref(), Some("Bears, on chain"));
+    assert_eq!(config.unique_dna_torrance, Some(10000));
+
+    let layers: Vec<Vec<(&str, Option<&str>)>> = config
+        .layer_configurations
+        .iter()
+        .map(|configuration| {
+            configuration
+                .layers_order
+                .iter()
+                .map(|layer| (layer.name.as_str(), layer.options.display_name.as_deref()))
+                .collect()
+        })
+        .collect();
+
+    assert_eq!(
+        layers,
+        [
+            vec![("Background", None), ("Eyes", Some("Eye Color"))],
+            vec![("Background", None)]
+        ]
+    );
+}
+
+#[test]
+fn imports_configurations_as_sets() {
+    let dir = std::env::temp_dir().join(format!("pix-hashlips-{}", std::process::id()));
+
+    let source = dir.join("hashlips");
+    let output = dir.join("pix");
+
+    fs::create_dir_all(source.join("src")).unwrap();
+    fs::create_dir_all(&output).unwrap();
+    fs::write(source.join("src/config.js"), CONFIG).unwrap();
+
+    for file in [
+        "Background/Blue#2.png",
+        "Background/Red.png",
+        "Eyes/Green#5.png",
+        "Eyes/Blue#Dark#3.png",
+        "Eyes/None#4.png",
+    ] {
+        let path = source.join("layers").join(file);
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        RgbaImage::new(1, 1).save(path).unwrap();
+    }
+
+    fs::copy(
+        source.join("layers/Background/Red.png"),
+        source.join("layers/Background/Gold.PNG"),
+    )
+    .unwrap();
+    fs::write(source.join("layers/Background/.DS_Store"), "").unwrap();
+
+    let (config, warnings) = hashlips::import(&source, &output).unwrap();
+
+    let sets: Vec<(String, usize, bool)> = config
+        .sets
+        .iter()
+        .flatten()
+        .map(|set| (set.name.clone(), set.amount, set.layers.is_some()))
+        .collect();
+
+    let images = output.join("images");
+
+    let copied = [
+        "set1/Background/Blue#2.png",
+        "set1/Background/Red#1.png",
+        "set1/Background/Gold#1.png",
+        "set1/Eyes/Green#5.png",
+        "set1/Eyes/Blue#3.png",
+        "set2/Background/Red#1.png",
+    ]
+    .map(|file| images.join(file).is_file());
+
+    let eyes_in_set2 = images.join("set2/Eyes").exists();
+
+    let none_copied = images.join("set1/Eyes/None#4.png").exists();
+
+    let set1_none = config.sets.as_ref().unwrap()[0].none.clone();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(config.name, "BasedBears");
+    assert_eq!(config.amount, 6);
+    assert_eq!(
+        sets,
+        [
+            (String::from("set1"), 4, false),
+            (String::from("set2"), 2, true)
+        ]
+    );
+    assert_eq!(copied, [true; 6]);
+    assert!(!eyes_in_set2);
+    assert!(!none_copied);
+    assert_eq!(set1_none, Some([(String::from("Eyes"), 4)].into()));
+    assert_eq!(
+        warnings,
+        ["layer Eyes of configuration 1 uses blend, opacity, which pix doesn't support, so it was left out"]
+    );
+}
+
+#[test]
+fn refuses_traits_pix_would_lose() {
+    let dir = std::env::temp_dir().join(format!("pix-hashlips-refuse-{}", std::process::id()));
+
+    let config = r#"
+const layerConfigurations = [
+  { growEditionSizeTo: 2, layersOrder: [{ name: "Background" }] },
+];
+"#;
+
+    let import = |files: &[&str]| {
+        let _ = fs::remove_dir_all(&dir);
+
+        let source = dir.join("hashlips");
+        let output = dir.join("pix");
+
+        fs::create_dir_all(source.join("layers/Background")).unwrap();
+        fs::create_dir_all(&output).unwrap();
+        fs::write(source.join("config.js"), config).unwrap();
+
+        for file in files {
+            fs::write(source.join("layers/Background").join(file), "").unwrap();
+        }
+
+        let error = hashlips::import(&source, &output).unwrap_err();
+
+        let copied = output.join("images").exists();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!copied);
+
+        format!("{:#}", error)
+    };
+
+    assert!(import(&["Blue.png", "Red.jpg"]).contains("Red.jpg is not a .png"));
+    assert!(import(&["Blue#Dark#2.png", "Blue#3.png"])
+        .contains("are both trait Blue of layer Background"));
+    assert!(import(&["Blue#2.5.png"]).contains("weight 2.5 is not a whole number"));
+}
+
+#[test]
+fn none_traits_become_none_weights() {
+    let dir = std::env::temp_dir().join(format!("pix-hashlips-none-{}", std::process::id()));
+
+    let source = dir.join("hashlips");
+    let output = dir.join("pix");
+
+    let config = r#"
+const layerConfigurations = [
+  { growEditionSizeTo: 2, layersOrder: [{ name: "Background" }, { name: "Hat" }, { name: "Glow" }] },
+];
+"#;
+
+    fs::create_dir_all(&source).unwrap();
+    fs::create_dir_all(&output).unwrap();
+    fs::write(source.join("config.js"), config).unwrap();
+
+    for file in [
+        "Background/Blue#2.png",
+        "Hat/Cap#3.png",
+        "Hat/None#7.png",
+        "Glow/None.png",
+    ] {
+        let path = source.join("layers").join(file);
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        RgbaImage::new(1, 1).save(path).unwrap();
+    }
+
+    let (config, _) = hashlips::import(&source, &output).unwrap();
+
+    let images = output.join("images");
+
+    let hat: Vec<_> = fs::read_dir(images.join("Hat"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+
+    let glow_is_empty = fs::read_dir(images.join("Glow")).unwrap().count() == 0;
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    let none: Vec<(&str, Option<u32>)> = config
+        .layers
+        .iter()
+        .map(|layer| (layer.name.as_str(), layer.none))
+        .collect();
+
+    assert_eq!(
+        none,
+        [("Background", None), ("Hat", Some(7)), ("Glow", Some(1))]
+    );
+    assert_eq!(hat, ["Cap#3.png"]);
+    assert!(glow_is_empty);
+}
@@ -0,0 +1,103 @@
+use std::fs;
+
+use pix::journal::{Journal, JournalEntry, JOURNAL};
+
+fn uploaded(nft_id: i32) -> JournalEntry {
+    JournalEntry::Uploaded {
+        nft_id,
+        nft_uid: None,
+        ipfs_hash: None,
+        image_hash: None,
+    }
+}
+
+#[test]
+fn records_are_appended_and_replayed() {
+    let dir = std::env::temp_dir().join(format!("pix-journal-{}", std::process::id()));
+
+    fs::create_dir_all(&dir).unwrap();
+
+    let config = dir.join("pix.json");
+    let config = config.to_str().unwrap();
+
+    let mut journal = Journal::load(config, String::from("42")).unwrap();
+
+    journal.record(1, uploaded(7)).unwrap();
+    journal
+        .record(
+            2,
+            JournalEntry::Failed {
+                error: String::from("boom"),
+            },
+        )
+        .unwrap();
+    journal.record(2, uploaded(8)).unwrap();
+
+    // one header and a line per record, nothing rewritten
+    let contents = fs::read_to_string(dir.join(JOURNAL)).unwrap();
+
+    assert_eq!(contents.lines().count(), 4);
+
+    // a line cut short by a crash is dropped
+    fs::write(
+        dir.join(JOURNAL),
+        format!("{}{{\"number\": 3, \"sta", contents),
+    )
+    .unwrap();
+
+    let mut journal = Journal::load(config, String::from("42")).unwrap();
+
+    assert!(matches!(
+        journal.get(1),
+        Some(JournalEntry::Uploaded { nft_id: 7, .. })
+    ));
+    assert!(matches!(
+        journal.get(2),
+        Some(JournalEntry::Uploaded { nft_id: 8, .. })
+    ));
+    assert!(journal.get(3).is_none());
+
+    journal.remove(2).unwrap();
+
+    let contents = fs::read_to_string(dir.join(JOURNAL)).unwrap();
+
+    assert_eq!(contents.lines().count(), 2);
+
+    assert!(Journal::load(config, String::from("43")).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn records_after_a_torn_line_are_kept() {
+    let dir = std::env::temp_dir().join(format!("pix-journal-torn-{}", std::process::id()));
+
+    fs::create_dir_all(&dir).unwrap();
+
+    let config = dir.join("pix.json");
+    let config = config.to_str().unwrap();
+
+    let mut journal = Journal::load(config, String::from("42")).unwrap();
+
+    journal.record(1, uploaded(7)).unwrap();
+
+    // cut short by a crash, without a trailing newline
+    let contents = fs::read_to_string(dir.join(JOURNAL)).unwrap();
+
+    fs::write(
+        dir.join(JOURNAL),
+        format!("{}{{\"number\": 2, \"sta", contents),
+    )
+    .unwrap();
+
+    let mut journal = Journal::load(config, String::from("42")).unwrap();
+
+    journal.record(3, uploaded(9)).unwrap();
+    journal.record(4, uploaded(10)).unwrap();
+
+    let journal = Journal::load(config, String::from("42")).unwrap();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(journal.tokens.keys().collect::<Vec<_>>(), [&1, &3, &4]);
+}
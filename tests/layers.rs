@@ -0,0 +1,123 @@
+use std::{fs, path::Path};
+
+use image::RgbaImage;
+use rand::{rngs::StdRng, SeedableRng};
+
+use pix::{
+    cli::Mode,
+    config::LayerConfig,
+    layers::{self, Layers},
+};
+
+fn png(path: &Path) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+    RgbaImage::new(4, 4).save(path).unwrap();
+}
+
+/// Pick 20 tokens with the same seed, returning their trait names
+fn pick(images: &Path, seed: u64) -> Vec<Vec<String>> {
+    let configs: Vec<LayerConfig> = ["background", "eyes"]
+        .iter()
+        .map(|name| LayerConfig {
+            name: name.to_string(),
+            ..Default::default()
+        })
+        .collect();
+
+    let mut layers = Layers::default();
+
+    layers
+        .load(Mode::Advanced, &configs, images.to_path_buf())
+        .unwrap();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    (0..20)
+        .map(|_| {
+            layers
+                .create_unique("", &mut rng)
+                .iter()
+                .enumerate()
+                .map(|(layer, index)| layers.data[layer][*index].name.clone())
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn same_seed_picks_the_same_traits() {
+    let dir = std::env::temp_dir().join(format!("pix-layers-{}", std::process::id()));
+
+    let traits = ["blue#10", "green#20", "red#30", "yellow#40"];
+
+    // the same images, written in opposite orders so the folders list them differently
+    for (images, order) in [("first", 0), ("second", 1)] {
+        for layer in ["background", "eyes"] {
+            let mut names = traits.to_vec();
+
+            if order == 1 {
+                names.reverse();
+            }
+
+            for name in names {
+                png(&dir.join(images).join(layer).join(format!("{}.png", name)));
+            }
+        }
+    }
+
+    let first = pick(&dir.join("first"), 42);
+
+    assert_eq!(first, pick(&dir.join("first"), 42));
+    assert_eq!(first, pick(&dir.join("second"), 42));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn loads_trait_images_named_none() {
+    let dir = std::env::temp_dir().join(format!("pix-layers-none-{}", std::process::id()));
+
+    png(&dir.join("hat/None#5.png"));
+    png(&dir.join("hat/cap#5.png"));
+
+    let mut layers = Layers::default();
+
+    let loaded = layers.load(
+        Mode::Advanced,
+        &[LayerConfig {
+            name: String::from("hat"),
+            ..Default::default()
+        }],
+        dir.clone(),
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    loaded.unwrap();
+
+    let names: Vec<&str> = layers.data[0].iter().map(|t| t.name.as_str()).collect();
+
+    assert_eq!(names, ["None", "cap"]);
+}
+
+#[test]
+fn trait_file_names_take_the_weight_after_the_first_hash() {
+    assert_eq!(
+        layers::parse_trait_file_name("laser#5").unwrap(),
+        ("laser", 5)
+    );
+    assert_eq!(
+        layers::parse_trait_file_name("laser#5#x").unwrap(),
+        ("laser", 5)
+    );
+
+    let error = |file_name| {
+        layers::parse_trait_file_name(file_name)
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert_eq!(error("laser"), "missing `#weight`");
+    assert_eq!(error("laser#x#5"), "x is not a parsable number");
+}
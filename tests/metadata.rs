@@ -0,0 +1,67 @@
+use serde_json::{Map, Value};
+
+use pix::{
+    config::{AppConfig, LayerConfig},
+    metadata,
+};
+
+#[test]
+fn placeholders_line_up_with_interleaved_groups() {
+    let layer = |name: &str, group: Option<&str>| LayerConfig {
+        name: name.to_owned(),
+        group: group.map(str::to_owned),
+        ..Default::default()
+    };
+
+    let config = AppConfig {
+        policy_id: Some("ab".repeat(28)),
+        layers: vec![
+            layer("a", Some("g")),
+            layer("b", None),
+            layer("c", Some("g")),
+        ],
+        ..Default::default()
+    };
+
+    let template: Value = serde_json::from_str(&metadata::build_template(&config)).unwrap();
+
+    let template = template["721"]["<policy_id>"]["<asset_name>"]["attributes"]
+        .as_object()
+        .unwrap();
+
+    // attributes of a token, inserted in layer order like `pix gen` does
+    let mut attributes = Map::new();
+
+    for layer in &config.layers {
+        let value = Value::String(layer.name.to_uppercase());
+
+        metadata::insert_attribute(
+            &mut attributes,
+            layer.group.as_deref(),
+            layer.name.clone(),
+            value,
+        );
+    }
+
+    let values = metadata::placeholder_values(&attributes);
+
+    let filled: Vec<(String, String)> = metadata::flatten(template)
+        .into_iter()
+        .map(|(path, placeholder)| {
+            let index: usize = placeholder
+                .as_str()
+                .and_then(|placeholder| placeholder.strip_prefix("<attribute"))
+                .and_then(|placeholder| placeholder.strip_suffix('>'))
+                .unwrap()
+                .parse()
+                .unwrap();
+
+            (path, values[index].clone())
+        })
+        .collect();
+
+    let expected = [("g.a", "A"), ("g.c", "C"), ("b", "B")]
+        .map(|(path, value)| (path.to_owned(), value.to_owned()));
+
+    assert_eq!(filled, expected);
+}
@@ -0,0 +1,441 @@
+mod common;
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use serde_json::json;
+
+use common::{MockServer, Response};
+use pix::{
+    config::{AppConfig, NftMakerNetwork, NftMakerProjectConfig, NftProjectId},
+    nft_maker::{
+        CreateProjectRequest, MetadataPlaceholder, NftMakerClient, NftMakerError, NftUpload,
+        NftUploadSubfile, RetryPolicy, UploadNftResponse,
+    },
+};
+
+fn client(server: &MockServer, network: NftMakerNetwork) -> NftMakerClient {
+    NftMakerClient::new(String::from("secret"), network)
+        .unwrap()
+        .with_base_url(Some(server.url.clone()))
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            jitter: false,
+        })
+}
+
+fn upload(client: &NftMakerClient) -> Result<UploadNftResponse, NftMakerError> {
+    client.upload_nft(
+        &NftProjectId::Id(42),
+        NftUpload {
+            asset_name: String::from("Bear1"),
+            mimetype: String::from("image/png"),
+            displayname: String::from("Bear #1"),
+            file_from_base64: String::from("aGVsbG8="),
+            ..Default::default()
+        },
+    )
+}
+
+fn uploaded() -> Response {
+    Response::json(
+        200,
+        json!({
+            "nftId": 7,
+            "nftUid": "nft-uid",
+            "ipfsHashMainnft": "QmHash",
+            "assetId": "asset"
+        }),
+    )
+}
+
+#[test]
+fn create_project_returns_the_new_project() {
+    let server = MockServer::start(|_| {
+        Response::json(200, json!({ "projectId": 12, "uid": "project-uid" }))
+    });
+
+    let config = AppConfig {
+        name: String::from("Bear"),
+        ..Default::default()
+    };
+
+    let project = NftMakerProjectConfig {
+        policy_locks_at: String::from("2030-01-01T00:00:00Z"),
+        tokenname_prefix: Some(String::from("Pix")),
+        ..Default::default()
+    };
+
+    let body = CreateProjectRequest::new(&config, String::from("{}"), &project);
+
+    let project = client(&server, NftMakerNetwork::Mainnet)
+        .create_project(&body)
+        .unwrap();
+
+    assert_eq!(project.project_id, 12);
+    assert_eq!(project.uid.as_deref(), Some("project-uid"));
+
+    let requests = server.requests();
+
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/CreateProject");
+    assert_eq!(requests[0].header("authorization"), Some("Bearer secret"));
+    assert_eq!(requests[0].json()["projectname"], "Bear");
+    assert_eq!(
+        requests[0].json()["policyLocksDateTime"],
+        "2030-01-01T00:00:00Z"
+    );
+    assert_eq!(requests[0].json()["tokennamePrefix"], "Pix");
+    assert_eq!(requests[0].json()["maxNftSupply"], 1);
+}
+
+#[test]
+fn legacy_create_project_puts_the_apikey_in_the_path() {
+    let server = MockServer::start(|_| Response::json(200, json!({ "projectId": 12 })));
+
+    let body = CreateProjectRequest::new(
+        &AppConfig::default(),
+        String::from("{}"),
+        &NftMakerProjectConfig::default(),
+    );
+
+    client(&server, NftMakerNetwork::Mainnet)
+        .with_legacy_api(true)
+        .create_project(&body)
+        .unwrap();
+
+    let request = &server.requests()[0];
+
+    assert_eq!(request.path, "/CreateProject/secret");
+    assert_eq!(request.header("authorization"), None);
+}
+
+#[test]
+fn upload_uses_v2_with_a_bearer_token() {
+    let server = MockServer::start(|_| uploaded());
+
+    let response = upload(&client(&server, NftMakerNetwork::Mainnet)).unwrap();
+
+    assert_eq!(response.nft_id, 7);
+    assert_eq!(response.ipfs_hash_mainnft.as_deref(), Some("QmHash"));
+
+    let request = &server.requests()[0];
+
+    assert_eq!(request.path, "/UploadNft/42");
+    assert_eq!(request.header("authorization"), Some("Bearer secret"));
+    assert_eq!(request.json()["tokenname"], "Bear1");
+    assert_eq!(
+        request.json()["previewImageNft"]["fileFromBase64"],
+        "aGVsbG8="
+    );
+}
+
+#[test]
+fn upload_sends_placeholders_or_a_metadata_override() {
+    let server = MockServer::start(|_| uploaded());
+
+    let client = client(&server, NftMakerNetwork::Testnet);
+
+    client
+        .upload_nft(
+            &NftProjectId::Id(42),
+            NftUpload {
+                asset_name: String::from("Bear1"),
+                metadata_placeholder: vec![MetadataPlaceholder {
+                    name: Some(String::from("attribute0")),
+                    value: Some(String::from("laser")),
+                }],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    client
+        .upload_nft(
+            &NftProjectId::Id(42),
+            NftUpload {
+                asset_name: String::from("Bear1"),
+                metadata: Some(String::from("{\"721\":{}}")),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    let requests = server.requests();
+
+    assert_eq!(
+        requests[0].json()["metadataPlaceholder"],
+        json!([{ "name": "attribute0", "value": "laser" }])
+    );
+    assert_eq!(requests[0].json()["metadataOverride"], json!(null));
+    assert_eq!(requests[1].json()["metadataOverride"], "{\"721\":{}}");
+}
+
+#[test]
+fn upload_attaches_subfiles() {
+    let server = MockServer::start(|_| uploaded());
+
+    let subfile = || NftUploadSubfile {
+        mimetype: String::from("video/mp4"),
+        file_from_base64: String::from("bW92aWU="),
+        description: Some(String::from("animation")),
+        metadata_placeholder: vec![MetadataPlaceholder {
+            name: Some(String::from("duration")),
+            value: Some(String::from("10s")),
+        }],
+    };
+
+    let upload = || NftUpload {
+        asset_name: String::from("Bear1"),
+        subfiles: vec![subfile()],
+        ..Default::default()
+    };
+
+    let project_id = NftProjectId::Id(42);
+
+    client(&server, NftMakerNetwork::Testnet)
+        .upload_nft(&project_id, upload())
+        .unwrap();
+
+    client(&server, NftMakerNetwork::Testnet)
+        .with_legacy_api(true)
+        .upload_nft(&project_id, upload())
+        .unwrap();
+
+    let requests = server.requests();
+
+    assert_eq!(
+        requests[0].json()["subfiles"],
+        json!([{
+            "subfile": {
+                "mimetype": "video/mp4",
+                "fileFromBase64": "bW92aWU=",
+                "fileFromsUrl": null,
+                "fileFromIPFS": null
+            },
+            "description": "animation",
+            "metadataPlaceholder": [{ "name": "duration", "value": "10s" }]
+        }])
+    );
+
+    let legacy_subfile = &requests[1].json()["subfiles"][0];
+
+    assert_eq!(legacy_subfile["mimetype"], "video/mp4");
+    assert_eq!(legacy_subfile["metadataPlaceholder"][0]["name"], "duration");
+}
+
+#[test]
+fn legacy_upload_puts_the_apikey_in_the_path() {
+    let server = MockServer::start(|_| uploaded());
+
+    upload(&client(&server, NftMakerNetwork::Mainnet).with_legacy_api(true)).unwrap();
+
+    let request = &server.requests()[0];
+
+    assert_eq!(request.path, "/UploadNft/secret/42");
+    assert_eq!(request.header("authorization"), None);
+    assert_eq!(request.json()["assetName"], "Bear1");
+}
+
+#[test]
+fn api_errors_carry_the_error_message() {
+    let server = MockServer::start(|_| {
+        Response::json(
+            406,
+            json!({
+                "resultState": "Error",
+                "errorCode": 12,
+                "errorMessage": "Tokenname already exists"
+            }),
+        )
+    });
+
+    match upload(&client(&server, NftMakerNetwork::Testnet)) {
+        Err(NftMakerError::Api { status, message }) => {
+            assert_eq!(status.as_u16(), 406);
+            assert_eq!(message, "Tokenname already exists");
+        }
+        other => panic!("expected an api error, got {:?}", other),
+    }
+
+    // client errors are not worth retrying
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn unauthorized_without_a_body_uses_the_status_reason() {
+    let server = MockServer::start(|_| Response {
+        status: 401,
+        headers: Vec::new(),
+        body: String::new(),
+    });
+
+    match upload(&client(&server, NftMakerNetwork::Testnet)) {
+        Err(NftMakerError::Api { status, message }) => {
+            assert_eq!(status.as_u16(), 401);
+            assert_eq!(message, "Unauthorized");
+        }
+        other => panic!("expected an api error, got {:?}", other),
+    }
+}
+
+fn nft() -> Response {
+    Response::json(200, json!({ "id": 7, "uid": "nft-uid", "state": "free" }))
+}
+
+#[test]
+fn rate_limits_and_server_errors_are_retried() {
+    let attempts = AtomicUsize::new(0);
+
+    let server = MockServer::start(move |_| match attempts.fetch_add(1, Ordering::SeqCst) {
+        0 => Response::json(429, json!({})).with_header("Retry-After", "0"),
+        1 => Response::json(503, json!({ "errorMessage": "down for maintenance" })),
+        _ => nft(),
+    });
+
+    let nft = client(&server, NftMakerNetwork::Testnet)
+        .get_nft(&NftProjectId::Id(42), 7, Some("nft-uid"))
+        .unwrap();
+
+    assert_eq!(nft.id, 7);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn uploads_are_only_retried_when_rate_limited() {
+    let attempts = AtomicUsize::new(0);
+
+    let server = MockServer::start(move |_| match attempts.fetch_add(1, Ordering::SeqCst) {
+        0 => Response::json(429, json!({})).with_header("Retry-After", "0"),
+        1 => Response::json(503, json!({ "errorMessage": "down for maintenance" })),
+        _ => uploaded(),
+    });
+
+    // the upload might have gone through before the 503, so trying again could mint it twice
+    match upload(&client(&server, NftMakerNetwork::Testnet)) {
+        Err(NftMakerError::Api { status, .. }) => assert_eq!(status.as_u16(), 503),
+        other => panic!("expected an api error, got {:?}", other),
+    }
+
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[test]
+fn retry_after_is_capped_and_understands_dates() {
+    let attempts = AtomicUsize::new(0);
+
+    let server = MockServer::start(move |_| match attempts.fetch_add(1, Ordering::SeqCst) {
+        // an hour, more than the policy's max delay of 5ms
+        0 => Response::json(429, json!({})).with_header("Retry-After", "3600"),
+        1 => Response::json(429, json!({}))
+            .with_header("Retry-After", "Wed, 21 Oct 2015 07:28:00 GMT"),
+        _ => uploaded(),
+    });
+
+    let started = std::time::Instant::now();
+
+    upload(&client(&server, NftMakerNetwork::Testnet)).unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn retries_give_up_after_max_attempts() {
+    let server =
+        MockServer::start(|_| Response::json(500, json!({ "errorMessage": "internal error" })));
+
+    let result = client(&server, NftMakerNetwork::Testnet).get_nft(
+        &NftProjectId::Id(42),
+        7,
+        Some("nft-uid"),
+    );
+
+    match result {
+        Err(NftMakerError::Api { status, message }) => {
+            assert_eq!(status.as_u16(), 500);
+            assert_eq!(message, "internal error");
+        }
+        other => panic!("expected an api error, got {:?}", other),
+    }
+
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn list_nfts_goes_through_every_page() {
+    let server = MockServer::start(|request| {
+        let count = if request.path.ends_with("/1") { 50 } else { 2 };
+
+        let nfts: Vec<_> = (0..count)
+            .map(|id| json!({ "id": id, "name": format!("Bear{}", id), "state": "free" }))
+            .collect();
+
+        Response::json(200, json!(nfts))
+    });
+
+    let nfts = client(&server, NftMakerNetwork::Mainnet)
+        .list_nfts(&NftProjectId::Id(42), "all")
+        .unwrap();
+
+    assert_eq!(nfts.len(), 52);
+
+    let requests = server.requests();
+
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method, "GET");
+    assert_eq!(requests[0].path, "/GetNfts/42/all/50/1");
+    assert_eq!(requests[1].path, "/GetNfts/42/all/50/2");
+    assert_eq!(requests[0].header("authorization"), Some("Bearer secret"));
+}
+
+#[test]
+fn get_nft_uses_the_uid_on_v2_and_the_id_on_legacy() {
+    let server = MockServer::start(|_| {
+        Response::json(
+            200,
+            json!({ "id": 7, "uid": "nft-uid", "state": "sold", "ipfshash": "QmHash" }),
+        )
+    });
+
+    let nft = client(&server, NftMakerNetwork::Mainnet)
+        .get_nft(&NftProjectId::Id(42), 7, Some("nft-uid"))
+        .unwrap();
+
+    assert_eq!(nft.status(), "sold");
+    assert!(nft.is_locked());
+
+    client(&server, NftMakerNetwork::Mainnet)
+        .with_legacy_api(true)
+        .get_nft(&NftProjectId::Id(42), 7, Some("nft-uid"))
+        .unwrap();
+
+    let requests = server.requests();
+
+    assert_eq!(requests[0].path, "/GetNftDetailsById/nft-uid");
+    assert_eq!(requests[1].path, "/GetNftDetailsById/secret/42/7");
+}
+
+#[test]
+fn delete_nft_uses_the_uid_on_v2_and_the_id_on_legacy() {
+    let server = MockServer::start(|_| Response::json(200, json!({})));
+
+    client(&server, NftMakerNetwork::Mainnet)
+        .delete_nft(&NftProjectId::Id(42), 7, Some("nft-uid"))
+        .unwrap();
+
+    client(&server, NftMakerNetwork::Mainnet)
+        .with_legacy_api(true)
+        .delete_nft(&NftProjectId::Id(42), 7, Some("nft-uid"))
+        .unwrap();
+
+    let requests = server.requests();
+
+    assert_eq!(requests[0].path, "/DeleteNft/nft-uid");
+    assert_eq!(requests[1].path, "/DeleteNft/secret/42/7");
+}
@@ -0,0 +1,261 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::json;
+
+use pix::{
+    config::{AppConfig, NftMakerNetwork},
+    policy::{self, NativeScript},
+    utils,
+};
+
+const KEY_HASH: &str = "8f3a8c8bba2d0e7c0c1c6b6ae8f6e8cdba2b6f3a9c61a0e1f3bd0c11";
+
+fn time_locked() -> NativeScript {
+    NativeScript::parse(
+        &json!({
+            "type": "all",
+            "scripts": [
+                { "type": "sig", "keyHash": KEY_HASH },
+                { "type": "before", "slot": 50000000 }
+            ]
+        })
+        .to_string(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn native_scripts_serialize_to_cbor() {
+    assert_eq!(
+        hex::encode(time_locked().to_cbor()),
+        format!("8201828200581c{}82051a02faf080", KEY_HASH)
+    );
+
+    let at_least = NativeScript::parse(
+        r#"{ "type": "atLeast", "required": 1, "scripts": [{ "type": "after", "slot": 24 }] }"#,
+    )
+    .unwrap();
+
+    assert_eq!(hex::encode(at_least.to_cbor()), "8303018182041818");
+}
+
+#[test]
+fn policy_id_hashes_the_tagged_script() {
+    assert_eq!(
+        time_locked().policy_id(),
+        "2bb745f8800418ba890563b2d8e5a322cd74d4dc1fa49b851e463707"
+    );
+}
+
+#[test]
+fn invalid_scripts_are_refused() {
+    assert!(NativeScript::parse(r#"{ "type": "sig", "keyHash": "abc" }"#).is_err());
+    assert!(NativeScript::parse(r#"{ "type": "atLeast", "required": 2, "scripts": [] }"#).is_err());
+    assert!(NativeScript::parse(r#"{ "type": "sometimes" }"#).is_err());
+}
+
+fn write(dir: &Path, name: &str, contents: serde_json::Value) -> PathBuf {
+    let path = dir.join(name);
+
+    fs::write(&path, contents.to_string()).unwrap();
+
+    path
+}
+
+#[test]
+fn load_policy_checks_the_policy_id_and_signer() {
+    let dir = std::env::temp_dir().join(format!("pix-policy-{}", std::process::id()));
+
+    fs::create_dir_all(&dir).unwrap();
+
+    let vkey_bytes: Vec<u8> = (0..32).collect();
+
+    // the key hash of the verification key above
+    let signer = "491112dd01155c07dab485f71b572e0cae759e2cd38b1c0e97554297";
+
+    let script_json = json!({ "type": "sig", "keyHash": signer });
+
+    let script = write(&dir, "policy.script", script_json.clone());
+
+    let skey = write(
+        &dir,
+        "policy.skey",
+        json!({
+            "type": "PaymentSigningKeyShelley_ed25519",
+            "description": "",
+            "cborHex": format!("5820{}", "00".repeat(32))
+        }),
+    );
+
+    let vkey = write(
+        &dir,
+        "policy.vkey",
+        json!({
+            "type": "PaymentVerificationKeyShelley_ed25519",
+            "description": "",
+            "cborHex": format!("5820{}", hex::encode(&vkey_bytes))
+        }),
+    );
+
+    let policy_id = NativeScript::parse(&script_json.to_string())
+        .unwrap()
+        .policy_id();
+
+    let policy = policy::load_policy(&script, &skey, &vkey, Some(&policy_id)).unwrap();
+
+    assert_eq!(policy.policy_id.as_deref(), Some(policy_id.as_str()));
+
+    let error = policy::load_policy(&script, &skey, &vkey, Some(KEY_HASH)).unwrap_err();
+
+    assert!(error.to_string().contains("but the config says"));
+
+    let other = write(
+        &dir,
+        "other.script",
+        json!({ "type": "sig", "keyHash": KEY_HASH }),
+    );
+
+    let other_id = NativeScript::parse(&json!({ "type": "sig", "keyHash": KEY_HASH }).to_string())
+        .unwrap()
+        .policy_id();
+
+    let error = policy::load_policy(&other, &skey, &vkey, Some(&other_id)).unwrap_err();
+
+    assert!(error.to_string().contains("is not a signer"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn lock_slot_follows_the_before_clauses() {
+    assert_eq!(time_locked().lock_slot(), Some(49999999));
+
+    let script = |json: serde_json::Value| NativeScript::parse(&json.to_string()).unwrap();
+
+    let sig = json!({ "type": "sig", "keyHash": KEY_HASH });
+
+    assert_eq!(script(sig.clone()).lock_slot(), None);
+
+    let any = script(json!({
+        "type": "any",
+        "scripts": [{ "type": "before", "slot": 10 }, { "type": "before", "slot": 20 }]
+    }));
+
+    assert_eq!(any.lock_slot(), Some(19));
+
+    let at_least = script(json!({
+        "type": "atLeast",
+        "required": 2,
+        "scripts": [
+            { "type": "before", "slot": 10 },
+            { "type": "before", "slot": 30 },
+            sig
+        ]
+    }));
+
+    assert_eq!(at_least.lock_slot(), Some(29));
+}
+
+#[test]
+fn slots_map_to_network_time() {
+    // the first Shelley slots
+    assert_eq!(
+        policy::slot_time(NftMakerNetwork::Mainnet, 4492800).to_rfc3339(),
+        "2020-07-29T21:44:51+00:00"
+    );
+    assert_eq!(
+        policy::slot_time(NftMakerNetwork::Testnet, 86400).to_rfc3339(),
+        "2022-06-21T00:00:00+00:00"
+    );
+}
+
+#[test]
+fn lock_date_comes_from_the_script() {
+    let script = |slot: u64| {
+        NativeScript::parse(
+            &json!({
+                "type": "all",
+                "scripts": [
+                    { "type": "sig", "keyHash": KEY_HASH },
+                    { "type": "before", "slot": slot }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap()
+    };
+
+    let network = NftMakerNetwork::Mainnet;
+
+    let lock = policy::slot_time(network, 300000000);
+
+    let lock_date = |policy_locks_at: Option<&str>| {
+        policy::lock_date(&script(300000000), network, policy_locks_at)
+    };
+
+    assert_eq!(lock_date(None).unwrap(), Some(lock));
+    assert_eq!(lock_date(Some(&lock.to_rfc3339())).unwrap(), Some(lock));
+
+    let error = lock_date(Some("2099-01-01")).unwrap_err();
+
+    assert!(error
+        .to_string()
+        .contains("doesn't match the policy script"));
+
+    // long past
+    assert!(policy::lock_date(&script(50000000), network, None).is_err());
+
+    let sig = NativeScript::parse(&json!({ "type": "sig", "keyHash": KEY_HASH }).to_string());
+
+    assert_eq!(
+        policy::lock_date(&sig.unwrap(), network, Some("2099-01-01")).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn policy_script_is_relative_to_the_config() {
+    let dir = std::env::temp_dir().join(format!("pix-policy-script-{}", std::process::id()));
+
+    fs::create_dir_all(dir.join("policy")).unwrap();
+
+    let script_json = json!({ "type": "sig", "keyHash": KEY_HASH });
+
+    write(&dir.join("policy"), "policy.script", script_json.clone());
+
+    let config = AppConfig {
+        policy_id: Some(
+            NativeScript::parse(&script_json.to_string())
+                .unwrap()
+                .policy_id(),
+        ),
+        policy_script: Some(PathBuf::from("policy/policy.script")),
+        ..Default::default()
+    };
+
+    // the tests don't run from the project folder
+    let script = config.validate_policy(&dir.join("pix.json")).unwrap();
+
+    assert!(script.is_some());
+
+    assert!(config.validate_policy(Path::new("pix.json")).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn relative_paths_walk_up_to_the_common_folder() {
+    let relative = |path: &str, base: &str| utils::relative_path(Path::new(path), Path::new(base));
+
+    assert_eq!(
+        relative("/keys/policy.script", "/projects/bears"),
+        Path::new("../../keys/policy.script")
+    );
+    assert_eq!(
+        relative("/projects/bears/policy.script", "/projects/bears"),
+        Path::new("policy.script")
+    );
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use std::time::Duration;
+
+use serde_json::json;
+
+use common::{MockServer, Response};
+use pix::{
+    cli::TokenRanges,
+    config::{AppConfig, NftMakerNetwork, NftMakerProjectConfig, NftProjectId},
+    journal::{Journal, JournalEntry},
+    nft_maker::{NftDetails, NftMakerClient, RetryPolicy},
+    remote::{self, Local, Status},
+};
+
+fn nft(id: i32, name: &str, ipfshash: &str) -> NftDetails {
+    serde_json::from_value(json!({
+        "id": id,
+        "name": name,
+        "state": "free",
+        "ipfshash": ipfshash
+    }))
+    .unwrap()
+}
+
+fn bear() -> AppConfig {
+    AppConfig {
+        name: String::from("Bear"),
+        ..Default::default()
+    }
+}
+
+fn uploaded(nft_id: i32, ipfs_hash: &str, image_hash: &str) -> JournalEntry {
+    JournalEntry::Uploaded {
+        nft_id,
+        nft_uid: None,
+        ipfs_hash: Some(String::from(ipfs_hash)),
+        image_hash: Some(String::from(image_hash)),
+    }
+}
+
+fn local(number: usize, image_hash: &str) -> Local {
+    Local {
+        number,
+        image_hash: Some(String::from(image_hash)),
+    }
+}
+
+#[test]
+fn diff_finds_missing_extra_and_mismatched_tokens() {
+    let mut journal = Journal::default();
+
+    journal.tokens.insert(1, uploaded(10, "QmOne", "one"));
+    journal.tokens.insert(2, uploaded(20, "QmTwo", "two"));
+    journal.tokens.insert(6, uploaded(60, "QmSix", "six"));
+
+    let nfts = vec![
+        nft(10, "Bear1", "QmOne"),
+        nft(20, "Bear2", "QmOther"),
+        nft(40, "Bear4", "QmFour"),
+        // not in the journal, matched by name
+        nft(50, "Bear5", "QmFive"),
+        nft(60, "Bear6", "QmSix"),
+    ];
+
+    let local = [
+        local(1, "one"),
+        local(2, "two"),
+        local(3, "three"),
+        local(5, "five"),
+        // re-rendered after it was uploaded
+        local(6, "new six"),
+    ];
+
+    let rows = remote::diff(&bear(), &local, &journal, &nfts);
+
+    let statuses: Vec<_> = rows.iter().map(|row| (row.number, row.status)).collect();
+
+    assert_eq!(
+        statuses,
+        vec![
+            (Some(1), Status::Ok),
+            (Some(2), Status::Mismatch),
+            (Some(3), Status::Missing),
+            (Some(5), Status::Ok),
+            (Some(6), Status::Mismatch),
+            (Some(4), Status::Extra),
+        ]
+    );
+}
+
+#[test]
+fn token_numbers_understand_the_tokenname_prefix() {
+    let config = AppConfig {
+        nft_maker_project: Some(NftMakerProjectConfig {
+            tokenname_prefix: Some(String::from("Based")),
+            ..Default::default()
+        }),
+        ..bear()
+    };
+
+    let nft = |name: Option<&str>, assetname: Option<&str>| -> NftDetails {
+        serde_json::from_value(json!({ "id": 1, "name": name, "assetname": assetname })).unwrap()
+    };
+
+    assert_eq!(
+        remote::token_number(&config, &nft(Some("Bear7"), None)),
+        Some(7)
+    );
+    assert_eq!(
+        remote::token_number(&config, &nft(Some("BasedBear7"), None)),
+        Some(7)
+    );
+    assert_eq!(
+        remote::token_number(&config, &nft(None, Some("BasedBear8"))),
+        Some(8)
+    );
+    assert_eq!(
+        remote::token_number(&config, &nft(Some("Cat7"), None)),
+        None
+    );
+}
+
+#[test]
+fn delete_refuses_sold_and_minted_tokens() {
+    let server = MockServer::start(|_| {
+        Response::json(
+            200,
+            json!([
+                { "id": 1, "name": "Bear1", "state": "free" },
+                { "id": 2, "name": "Bear2", "state": "sold" },
+                { "id": 3, "name": "Bear3", "state": "reserved", "minted": true }
+            ]),
+        )
+    });
+
+    let client = NftMakerClient::new(String::from("secret"), NftMakerNetwork::Mainnet)
+        .unwrap()
+        .with_base_url(Some(server.url.clone()))
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+            jitter: false,
+        });
+
+    let error = remote::delete(
+        &client,
+        &NftProjectId::Id(42),
+        &bear(),
+        &mut Journal::default(),
+        &[1, 2, 3],
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "refusing to delete sold or minted tokens: 2 (sold), 3 (minted)"
+    );
+
+    // nothing was deleted
+    assert!(server
+        .requests()
+        .iter()
+        .all(|request| request.path.starts_with("/GetNfts")));
+}
+
+#[test]
+fn token_ranges_parse_lists_and_ranges() {
+    assert_eq!(
+        "1-3,8, 5,2".parse::<TokenRanges>(),
+        Ok(TokenRanges(vec![1, 2, 3, 5, 8]))
+    );
+
+    assert!("3-1".parse::<TokenRanges>().is_err());
+    assert!("a".parse::<TokenRanges>().is_err());
+    assert!("".parse::<TokenRanges>().is_err());
+    assert!("1-10000000000".parse::<TokenRanges>().is_err());
+    assert!("1-99999,100001-100002".parse::<TokenRanges>().is_err());
+    assert_eq!(
+        "1-100000"
+            .parse::<TokenRanges>()
+            .map(|ranges| ranges.0.len()),
+        Ok(TokenRanges::MAX)
+    );
+}
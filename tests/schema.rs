@@ -0,0 +1,44 @@
+use serde_json::json;
+
+use pix::{config::AppConfig, schema};
+
+#[test]
+fn default_config_is_valid() {
+    let config = serde_json::to_value(AppConfig::default()).unwrap();
+
+    schema::validate(&config).unwrap();
+}
+
+#[test]
+fn errors_point_at_the_field() {
+    let mut config = serde_json::to_value(AppConfig::default()).unwrap();
+
+    config["mode"] = json!("fancy");
+    config["nft_maker"] = json!({ "network": "foo", "nft_project_id": 1 });
+
+    let error = schema::validate(&config).unwrap_err().to_string();
+
+    assert!(error.contains("at /mode:"), "{}", error);
+    assert!(error.contains("at /nft_maker/network:"), "{}", error);
+}
+
+#[test]
+fn explicit_nulls_are_left_out_fields() {
+    let mut config = serde_json::to_value(AppConfig::default()).unwrap();
+
+    config["policy_id"] = json!(null);
+    config["metadata"] = json!({ "name": null, "description": "A bear" });
+
+    schema::validate(&config).unwrap();
+
+    // a null where a value is required is still reported
+    config["name"] = json!(null);
+
+    let error = schema::validate(&config).unwrap_err().to_string();
+
+    assert!(
+        error.contains("\"name\" is a required property"),
+        "{}",
+        error
+    );
+}
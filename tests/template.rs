@@ -0,0 +1,149 @@
+use serde_json::{json, Map, Value};
+
+use pix::{
+    config::{AppConfig, LayerConfig, MetadataConfig},
+    metadata,
+    template::{self, Template},
+};
+
+fn vars(value: Value) -> Map<String, Value> {
+    match value {
+        Value::Object(map) => map,
+        _ => unreachable!(),
+    }
+}
+
+fn render(source: &str, value: Value) -> String {
+    template::render(source, &vars(value)).unwrap()
+}
+
+#[test]
+fn inserts_variables() {
+    let rendered = render(
+        "{{name}} #{{ number }}",
+        json!({ "name": "Bear", "number": 7 }),
+    );
+
+    assert_eq!(rendered, "Bear #7");
+}
+
+#[test]
+fn pads_variables() {
+    let value = json!({ "number": 42 });
+
+    assert_eq!(render("{{number:05}}", value.clone()), "00042");
+    assert_eq!(render("{{number:5}}", value.clone()), "   42");
+    assert_eq!(render("{{number:1}}", value), "42");
+}
+
+#[test]
+fn falls_back_to_defaults() {
+    let value = json!({ "eyes": "None", "hat": "", "mouth": null, "nose": "red" });
+
+    assert_eq!(render("{{eyes|plain}}", value.clone()), "plain");
+    assert_eq!(render("{{hat|bare}}", value.clone()), "bare");
+    assert_eq!(render("{{mouth|closed}}", value.clone()), "closed");
+    assert_eq!(render("{{missing|?}}", value.clone()), "?");
+    assert_eq!(render("{{nose|plain}}", value), "red");
+}
+
+#[test]
+fn keeps_literal_false_values() {
+    let value = json!({ "mood": "false", "shiny": false });
+
+    assert_eq!(render("{{mood|happy}}", value.clone()), "false");
+    assert_eq!(render("{{shiny|no}}", value.clone()), "no");
+    assert_eq!(render("{{#if mood}}set{{/if}}", value), "set");
+}
+
+#[test]
+fn renders_conditions() {
+    let source = r#"{{#if eyes}}{{eyes}}{{else}}closed{{/if}}"#;
+
+    assert_eq!(render(source, json!({ "eyes": "laser" })), "laser");
+    assert_eq!(render(source, json!({ "eyes": "None" })), "closed");
+    assert_eq!(render(source, json!({})), "closed");
+
+    let source = r#"{{#if eyes == "laser"}}pew{{/if}}{{#if eyes != "laser"}}blink{{/if}}"#;
+
+    assert_eq!(render(source, json!({ "eyes": "laser" })), "pew");
+    assert_eq!(render(source, json!({ "eyes": "sleepy" })), "blink");
+}
+
+#[test]
+fn nests_conditions() {
+    let source = "{{#if hat}}{{#if eyes}}both{{else}}hat{{/if}}{{else}}none{{/if}}";
+
+    assert_eq!(
+        render(source, json!({ "hat": "cap", "eyes": "laser" })),
+        "both"
+    );
+    assert_eq!(render(source, json!({ "hat": "cap" })), "hat");
+    assert_eq!(render(source, json!({ "eyes": "laser" })), "none");
+}
+
+#[test]
+fn escapes_braces() {
+    assert_eq!(render(r"\{{name}}", json!({ "name": "Bear" })), "{{name}}");
+    assert_eq!(render("{ name }}", json!({})), "{ name }}");
+}
+
+#[test]
+fn rejects_broken_templates() {
+    for source in [
+        "{{name",
+        "{{}}",
+        "{{#if}}x{{/if}}",
+        "{{#if eyes}}x",
+        "{{#if eyes}}x{{else}}y",
+        "x{{/if}}",
+        "{{else}}",
+    ] {
+        assert!(Template::parse(source).is_err(), "{}", source);
+    }
+}
+
+#[test]
+fn lists_variables() {
+    let template =
+        Template::parse("{{a}}{{#if b == \"x\"}}{{c:03}}{{else}}{{d|e}}{{/if}}").unwrap();
+
+    assert_eq!(template.variables(), ["a", "b", "c", "d"]);
+}
+
+fn config(name: &str) -> AppConfig {
+    let layer = |name: &str, group: Option<&str>| LayerConfig {
+        name: name.to_owned(),
+        group: group.map(str::to_owned),
+        ..Default::default()
+    };
+
+    AppConfig {
+        layers: vec![layer("background", None), layer("eyes", Some("face"))],
+        metadata: Some(MetadataConfig {
+            name: Some(name.to_owned()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn validates_variable_names() {
+    for name in [
+        "{{display_name}} #{{number:05}} of {{amount}}",
+        "{{background}} {{eyes}} {{face.eyes}} {{rank}} {{name}}",
+    ] {
+        assert!(
+            metadata::validate_templates(&config(name)).is_ok(),
+            "{}",
+            name
+        );
+    }
+
+    for name in ["{{nubmer}}", "{{#if eys}}x{{/if}}", "{{set}}"] {
+        let error = metadata::validate_templates(&config(name)).unwrap_err();
+
+        assert!(error.to_string().contains("unknown variable"), "{}", name);
+    }
+}